@@ -7,11 +7,50 @@ fn criterion_benchmark(c: &mut Criterion) {
     let input: Vec<u32> = (&mut rng).sample_iter(Uniform::from(0..16)).take(1 << 20).collect();
     let mut output = Vec::with_capacity(input.len());
     group.throughput(Throughput::Elements(input.len() as u64));
-    group.bench_function("avx2", |b| b.iter(|| filter_vec::avx2::filter_vec(&input, 4..=12, &mut output)));
-    // group.bench_function("avx512", |b| b.iter(|| filter_vec::avx512::filter_vec(&input, 4..=12, &mut output)));
+    if is_x86_feature_detected!("avx2") {
+        group.bench_function("avx2", |b| b.iter(|| filter_vec::avx2::filter_vec(&input, 4..=12, &mut output)));
+    }
+    group.bench_function("dispatch", |b| b.iter(|| filter_vec::filter_vec(&input, 4..=12, &mut output)));
+    if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw") {
+        group.bench_function("avx512", |b| b.iter(|| filter_vec::avx512::filter_vec(&input, 4..=12, &mut output)));
+    }
     group.bench_function("scalar_iterator", |b| b.iter(|| filter_vec::filter_vec_iter(&input, 4..=12, &mut output)));
     group.bench_function("scalar_forloop", |b| b.iter(|| filter_vec::filter_vec_scalar(&input, 4..=12, &mut output)));
     group.bench_function("scalar_nobranch", |b| b.iter(|| filter_vec::filter_vec_nobranch(&input, 4..=12, &mut output)));
+
+    // The same cache-resident inputs and ranges as count-interval below, so the two
+    // groups differ by the compaction only.
+    let input: Vec<u32> = (&mut rng).sample_iter(Uniform::from(0..=u32::MAX)).take(1 << 16).collect();
+    let input_i64: Vec<i64> = (&mut rng).sample_iter(Uniform::from(i64::MIN..=i64::MAX)).take(1 << 16).collect();
+    let input_u8: Vec<u8> = (&mut rng).sample_iter(Uniform::from(0..=u8::MAX)).take(1 << 16).collect();
+    group.throughput(Throughput::Elements(input.len() as u64));
+    if is_x86_feature_detected!("avx2") {
+        group.bench_function("avx2_u32", |b| b.iter(|| filter_vec::avx2::filter_vec(&input, 1 << 30..=3 << 30, &mut output)));
+        group.bench_function("avx2_i64", |b| b.iter(|| filter_vec::avx2::filter_vec(&input_i64, -1 << 62..=1 << 62, &mut output)));
+        group.bench_function("avx2_u8", |b| b.iter(|| filter_vec::avx2::filter_vec(&input_u8, 64..=192, &mut output)));
+    }
+    if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw") {
+        group.bench_function("avx512_u32", |b| b.iter(|| filter_vec::avx512::filter_vec(&input, 1 << 30..=3 << 30, &mut output)));
+        group.bench_function("avx512_i64", |b| b.iter(|| filter_vec::avx512::filter_vec(&input_i64, -1 << 62..=1 << 62, &mut output)));
+        group.bench_function("avx512_u8", |b| b.iter(|| filter_vec::avx512::filter_vec(&input_u8, 64..=192, &mut output)));
+    }
+    group.finish();
+
+    // Counting skips the compaction and, with cache-resident inputs, isolates the cost
+    // of the range test itself.
+    let mut group = c.benchmark_group("count-interval");
+    group.throughput(Throughput::Elements(input.len() as u64));
+    if is_x86_feature_detected!("avx2") {
+        group.bench_function("avx2_u32", |b| b.iter(|| filter_vec::avx2::count_in_range(&input, 1 << 30..=3 << 30)));
+        group.bench_function("avx2_i64", |b| b.iter(|| filter_vec::avx2::count_in_range(&input_i64, -1 << 62..=1 << 62)));
+        group.bench_function("avx2_u8", |b| b.iter(|| filter_vec::avx2::count_in_range(&input_u8, 64..=192)));
+    }
+    if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw") {
+        group.bench_function("avx512_u32", |b| b.iter(|| filter_vec::avx512::count_in_range(&input, 1 << 30..=3 << 30)));
+        group.bench_function("avx512_i64", |b| b.iter(|| filter_vec::avx512::count_in_range(&input_i64, -1 << 62..=1 << 62)));
+        group.bench_function("avx512_u8", |b| b.iter(|| filter_vec::avx512::count_in_range(&input_u8, 64..=192)));
+    }
+    group.finish();
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -1,75 +1,1413 @@
+//! AVX2 kernels.
+//!
+//! Each function is the AVX2 version of the function of the same name at the crate
+//! root, where its behavior is documented. They panic if the CPU does not support AVX2.
+
 use std::arch::x86_64::__m256i as DataType;
 use std::arch::x86_64::_mm256_add_epi32 as op_add;
-use std::arch::x86_64::_mm256_cmpgt_epi32 as op_greater;
 use std::arch::x86_64::_mm256_lddqu_si256 as load_unaligned;
 use std::arch::x86_64::_mm256_or_si256 as op_or;
-use std::arch::x86_64::_mm256_set1_epi32 as set1;
 use std::arch::x86_64::_mm256_storeu_si256 as store_unaligned;
+use std::arch::x86_64::_mm256_xor_si256 as op_xor;
 use std::arch::x86_64::*;
-use std::ops::RangeInclusive;
+use std::mem::MaybeUninit;
+use std::ops::{RangeBounds, RangeInclusive};
+
+use crate::private::Sealed;
+use crate::set::U32Set;
 
+/// Number of ids compacted by a single permutation.
 const NUM_LANES: usize = 8;
+const SIGN_BIT_8: u8 = 1 << 7;
+const SIGN_BIT_16: u16 = 1 << 15;
+const SIGN_BIT_32: u32 = 1 << 31;
+const SIGN_BIT_64: u64 = 1 << 63;
+
+/// Element types with AVX2 kernels.
+///
+/// This trait is sealed, it is implemented for every [`FilterElement`](crate::FilterElement).
+pub trait Avx2Element: Sealed + Copy + PartialOrd {
+    /// Number of elements in a 256-bit word.
+    const NUM_LANES: usize;
+
+    #[doc(hidden)]
+    type SimdRange;
+
+    /// `range` must not be empty.
+    #[doc(hidden)]
+    unsafe fn simd_range(range: &RangeInclusive<Self>) -> Self::SimdRange;
+
+    /// Loads the word starting at `word`, and returns the bitset of its
+    /// elements that belong to `range`.
+    #[doc(hidden)]
+    unsafe fn compute_filter_bitset(word: *const Self, range: &Self::SimdRange) -> u32;
+
+    #[doc(hidden)]
+    type SimdValue;
+
+    #[doc(hidden)]
+    unsafe fn splat(value: Self) -> Self::SimdValue;
+
+    /// Loads the word starting at `word`, and returns the bitset of its
+    /// elements equal to `value`.
+    #[doc(hidden)]
+    unsafe fn compute_eq_bitset(word: *const Self, value: &Self::SimdValue) -> u32;
+
+    /// Writes at `output_tail` the elements of the word starting at `word` that
+    /// are set in `keeper_bitset`, and returns the new tail.
+    ///
+    /// Up to `NUM_LANES` slots are written, whatever the number of elements kept.
+    #[doc(hidden)]
+    unsafe fn write_kept(output_tail: *mut Self, word: *const Self, keeper_bitset: u32) -> *mut Self;
+}
 
-pub fn filter_vec(input: &[u32], range: RangeInclusive<u32>, output: &mut Vec<u32>) {
-    assert_eq!(input.len() % NUM_LANES, 0);
-    // We restrict the accepted bondary, because unsigned integers & SIMD don't
-    // play well.
-    let accepted_range = 0u32..(i32::MAX as u32);
-    assert!(accepted_range.contains(range.start()));
-    assert!(accepted_range.contains(range.end()));
+/// AVX2 version of [`crate::filter_vec`].
+pub fn filter_vec<T: Avx2Element>(input: &[T], range: impl RangeBounds<T>, output: &mut Vec<u32>) {
+    filter_vec_with_offset(input, range, 0, output);
+}
+
+/// AVX2 version of [`crate::filter_vec_with_offset`].
+pub fn filter_vec_with_offset<T: Avx2Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    first_id: u32,
+    output: &mut Vec<u32>,
+) {
     output.clear();
-    output.reserve(input.len());
-    let num_words = input.len() / NUM_LANES;
+    filter_vec_append(input, range, first_id, output);
+}
+
+/// AVX2 version of [`crate::filter_vec_append`].
+pub fn filter_vec_append<T: Avx2Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    first_id: u32,
+    output: &mut Vec<u32>,
+) {
+    assert!(is_x86_feature_detected!("avx2"), "AVX2 is not available on this CPU");
+    crate::assert_ids_fit(first_id, input.len());
+    let Some(range) = crate::to_inclusive(range) else {
+        return;
+    };
     unsafe {
+        append_in_range(input, range, first_id, output);
+    }
+}
+
+/// Appends to `output` the ids of the elements of `input` that belong to `range`.
+///
+/// # Safety
+///
+/// The CPU must support AVX2. `range` must not be empty, and the ids of `input` must
+/// fit in a `u32`.
+pub(crate) unsafe fn append_in_range<T: Avx2Element>(
+    input: &[T],
+    range: RangeInclusive<T>,
+    first_id: u32,
+    output: &mut Vec<u32>,
+) {
+    append_ids(input, &InRange::new(range), first_id, output);
+}
+
+/// Appends to `output` the ids of the elements of `input` that pass `predicate`.
+///
+/// # Safety
+///
+/// The CPU must support AVX2.
+unsafe fn append_ids<T: Avx2Element, P: Predicate<T>>(
+    input: &[T],
+    predicate: &P,
+    first_id: u32,
+    output: &mut Vec<u32>,
+) {
+    for (block_id, block) in input.chunks(crate::OUTPUT_BLOCK_LEN).enumerate() {
+        output.reserve(block.len());
+        let initial_len = output.len();
         let output_len = filter_vec_avx2_aux(
-            input.as_ptr() as *const __m256i,
-            range,
-            output.as_mut_ptr(),
-            num_words,
+            block.as_ptr(),
+            block.len(),
+            predicate,
+            first_id + (block_id * crate::OUTPUT_BLOCK_LEN) as u32,
+            output.as_mut_ptr().add(initial_len),
         );
-        output.set_len(output_len);
+        output.set_len(initial_len + output_len);
     }
 }
 
-unsafe fn filter_vec_avx2_aux(
-    mut input: *const __m256i,
-    range: RangeInclusive<u32>,
+/// Writes at `output` the ids of the elements of `input` that pass `predicate`, and
+/// returns their number.
+///
+/// `output` must have room for `input_len` ids, and no more: ids are stored a full
+/// register at a time, so the slots past the returned number may be overwritten,
+/// but the stores of word `i` never go beyond the `T::NUM_LANES * (i + 1)` first
+/// slots, and the tail is written one id at a time.
+#[target_feature(enable = "avx2")]
+unsafe fn filter_vec_avx2_aux<T: Avx2Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+    first_id: u32,
     output: *mut u32,
-    num_words: usize,
 ) -> usize {
+    let num_words = input_len / T::NUM_LANES;
     let mut output_tail = output;
-    let range_simd = set1(*range.start() as i32)..=set1(*range.end() as i32);
-    let mut ids = from_u32x8([0, 1, 2, 3, 4, 5, 6, 7]);
-    const SHIFT: __m256i = from_u32x8([NUM_LANES as u32; NUM_LANES]);
-    for _ in 0..num_words {
-        let word = load_unaligned(input);
-        let keeper_bitset = compute_filter_bitset(word, range_simd.clone());
-        let added_len = keeper_bitset.count_ones();
-        let filtered_doc_ids = compact(ids, keeper_bitset);
-        store_unaligned(
-            output_tail as *mut __m256i,
-            filtered_doc_ids,
-        );
-        output_tail = output_tail.offset(added_len as isize);
-        ids = op_add(ids, SHIFT);
-        input = input.offset(1);
+    let mut ids = op_add(
+        from_u32x8([0, 1, 2, 3, 4, 5, 6, 7]),
+        _mm256_set1_epi32(first_id as i32),
+    );
+    for word_id in 0..num_words {
+        let word = input.add(word_id * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset(word);
+        output_tail = write_ids(output_tail, &mut ids, keeper_bitset, T::NUM_LANES);
     }
+    scalar_tail(input, input_len, predicate, |id, _, keep| {
+        *output_tail = first_id + id as u32;
+        output_tail = output_tail.add(keep as usize);
+    });
     output_tail.offset_from(output) as usize
 }
 
+/// Writes at `output_tail` the ids of the `num_lanes` elements following `ids`
+/// that are set in `keeper_bitset`, and returns the new tail.
+///
+/// Ids are stored a full register at a time: `num_lanes` slots are written,
+/// whatever the number of ids kept.
 #[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn write_ids(
+    mut output_tail: *mut u32,
+    ids: &mut DataType,
+    mut keeper_bitset: u32,
+    num_lanes: usize,
+) -> *mut u32 {
+    const SHIFT: DataType = from_u32x8([NUM_LANES as u32; NUM_LANES]);
+    const HALF_SHIFT: DataType = from_u32x8([NUM_LANES as u32 / 2; NUM_LANES]);
+    if num_lanes == NUM_LANES / 2 {
+        let filtered_doc_ids = compact_4(_mm256_castsi256_si128(*ids), keeper_bitset as u8);
+        _mm_storeu_si128(output_tail as *mut __m128i, filtered_doc_ids);
+        *ids = op_add(*ids, HALF_SHIFT);
+        return output_tail.add(keeper_bitset.count_ones() as usize);
+    }
+    for _ in 0..num_lanes / NUM_LANES {
+        let keeper = keeper_bitset as u8;
+        let filtered_doc_ids = compact(*ids, keeper);
+        store_unaligned(output_tail as *mut DataType, filtered_doc_ids);
+        output_tail = output_tail.add(keeper.count_ones() as usize);
+        *ids = op_add(*ids, SHIFT);
+        keeper_bitset >>= NUM_LANES;
+    }
+    output_tail
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
 unsafe fn compact(data: DataType, mask: u8) -> DataType {
     let vperm_mask = MASK_TO_PERMUTATION[mask as usize];
     _mm256_permutevar8x32_epi32(data, vperm_mask)
 }
 
+/// Compacts the 4 lanes of `data`, used by the kernels over 64-bit elements.
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn compact_4(data: __m128i, mask: u8) -> __m128i {
+    let vperm_mask = MASK_TO_PERMUTATION_4[mask as usize];
+    _mm_castps_si128(_mm_permutevar_ps(_mm_castsi128_ps(data), vperm_mask))
+}
+
+// -------------------------------------------------------------------------------------------
+// Bitset output
+
+/// AVX2 version of [`crate::filter_to_bitset`].
+pub fn filter_to_bitset<T: Avx2Element>(input: &[T], range: impl RangeBounds<T>, bitset: &mut [u64]) {
+    assert!(is_x86_feature_detected!("avx2"), "AVX2 is not available on this CPU");
+    crate::assert_bitset_fits(input.len(), bitset.len());
+    let Some(range) = crate::to_inclusive(range) else {
+        bitset[..input.len().div_ceil(64)].fill(0);
+        return;
+    };
+    unsafe {
+        let predicate = InRange::new(range);
+        filter_to_bitset_avx2_aux(input.as_ptr(), input.len(), &predicate, bitset.as_mut_ptr());
+    }
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn filter_to_bitset_avx2_aux<T: Avx2Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+    bitset: *mut u64,
+) {
+    // `T::NUM_LANES` divides 64, so each u64 of the bitset is made of whole words.
+    let words_per_u64 = 64 / T::NUM_LANES;
+    let num_full_u64 = input_len / 64;
+    for u64_id in 0..num_full_u64 {
+        let mut bits = 0u64;
+        for word_id in 0..words_per_u64 {
+            let word = input.add(u64_id * 64 + word_id * T::NUM_LANES);
+            let keeper_bitset = predicate.compute_filter_bitset(word) as u64;
+            bits |= keeper_bitset << (word_id * T::NUM_LANES);
+        }
+        *bitset.add(u64_id) = bits;
+    }
+    let tail_start = num_full_u64 * 64;
+    if tail_start == input_len {
+        return;
+    }
+    let mut bits = 0u64;
+    let num_tail_words = (input_len - tail_start) / T::NUM_LANES;
+    for word_id in 0..num_tail_words {
+        let word = input.add(tail_start + word_id * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset(word) as u64;
+        bits |= keeper_bitset << (word_id * T::NUM_LANES);
+    }
+    scalar_tail(input, input_len, predicate, |id, _, keep| {
+        bits |= (keep as u64) << (id - tail_start);
+    });
+    *bitset.add(num_full_u64) = bits;
+}
+
+// -------------------------------------------------------------------------------------------
+// Count
+
+/// AVX2 version of [`crate::count_in_range`].
+///
+/// Also requires POPCNT, which every AVX2 CPU has.
+pub fn count_in_range<T: Avx2Element>(input: &[T], range: impl RangeBounds<T>) -> usize {
+    assert!(is_x86_feature_detected!("avx2"), "AVX2 is not available on this CPU");
+    assert!(is_x86_feature_detected!("popcnt"), "POPCNT is not available on this CPU");
+    let Some(range) = crate::to_inclusive(range) else {
+        return 0;
+    };
+    unsafe { count_avx2_aux(input.as_ptr(), input.len(), &InRange::new(range)) }
+}
+
+#[target_feature(enable = "avx2,popcnt")]
+unsafe fn count_avx2_aux<T: Avx2Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+) -> usize {
+    let num_words = input_len / T::NUM_LANES;
+    let mut count = 0;
+    for word_id in 0..num_words {
+        let word = input.add(word_id * T::NUM_LANES);
+        count += predicate.compute_filter_bitset(word).count_ones() as usize;
+    }
+    scalar_tail(input, input_len, predicate, |_, _, keep| count += keep as usize);
+    count
+}
+
+// -------------------------------------------------------------------------------------------
+// Equality
+
+/// AVX2 version of [`crate::filter_eq`].
+pub fn filter_eq<T: Avx2Element>(input: &[T], value: T, output: &mut Vec<u32>) {
+    assert!(is_x86_feature_detected!("avx2"), "AVX2 is not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    output.clear();
+    unsafe {
+        append_ids(input, &Equal::new(value), 0, output);
+    }
+}
+
+/// AVX2 version of [`crate::filter_ne`].
+pub fn filter_ne<T: Avx2Element>(input: &[T], value: T, output: &mut Vec<u32>) {
+    assert!(is_x86_feature_detected!("avx2"), "AVX2 is not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    output.clear();
+    unsafe {
+        append_ids(input, &NotEqual(Equal::new(value)), 0, output);
+    }
+}
+
+// -------------------------------------------------------------------------------------------
+// Multiple ranges
+
+/// AVX2 version of [`crate::filter_vec_multi_range`].
+pub fn filter_vec_multi_range<T: Avx2Element>(
+    input: &[T],
+    ranges: &[RangeInclusive<T>],
+    output: &mut Vec<u32>,
+) {
+    assert!(is_x86_feature_detected!("avx2"), "AVX2 is not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    output.clear();
+    let ranges: Vec<RangeInclusive<T>> = ranges
+        .iter()
+        .filter(|range| !range.is_empty())
+        .cloned()
+        .collect();
+    if ranges.is_empty() {
+        return;
+    }
+    unsafe {
+        append_ids(input, &AnyInRange::new(ranges), 0, output);
+    }
+}
+
+// -------------------------------------------------------------------------------------------
+// Negated range
+
+/// AVX2 version of [`crate::filter_vec_outside`].
+pub fn filter_vec_outside<T: Avx2Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    output: &mut Vec<u32>,
+) {
+    assert!(is_x86_feature_detected!("avx2"), "AVX2 is not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    output.clear();
+    let Some(range) = crate::to_inclusive(range) else {
+        output.extend(0..input.len() as u32);
+        return;
+    };
+    unsafe {
+        append_ids(input, &Outside(InRange::new(range)), 0, output);
+    }
+}
+
+// -------------------------------------------------------------------------------------------
+// Set membership
+
+/// AVX2 version of [`crate::filter_in_set`].
+pub fn filter_in_set(input: &[u32], values: &[u32], output: &mut Vec<u32>) {
+    assert!(is_x86_feature_detected!("avx2"), "AVX2 is not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    output.clear();
+    let set = U32Set::new(values);
+    unsafe {
+        match &set {
+            U32Set::Empty => {}
+            U32Set::Broadcast(values) => append_ids(input, &AnyOf::new(values), 0, output),
+            U32Set::Sorted(values) => append_ids(input, &SortedSearch { set: &set, values }, 0, output),
+            U32Set::Bitmap { min, span, words } => {
+                let predicate = BitmapLookup::new(&set, *min, *span, words);
+                append_ids(input, &predicate, 0, output);
+            }
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------
+// Selection
+
+/// AVX2 version of [`crate::filter_selection`].
+pub fn filter_selection(
+    column: &[u32],
+    candidate_ids: &[u32],
+    range: impl RangeBounds<u32>,
+    output: &mut Vec<u32>,
+) {
+    assert!(is_x86_feature_detected!("avx2"), "AVX2 is not available on this CPU");
+    crate::assert_ids_in_bounds(candidate_ids, column.len());
+    output.clear();
+    let Some(range) = crate::to_inclusive(range) else {
+        return;
+    };
+    if column.len() > i32::MAX as usize {
+        // Gather indices are signed 32-bit integers.
+        return crate::scalar::filter_selection(column, candidate_ids, range, output);
+    }
+    unsafe {
+        let range_simd = InRange::new(range);
+        for block in candidate_ids.chunks(crate::OUTPUT_BLOCK_LEN) {
+            output.reserve(block.len());
+            let initial_len = output.len();
+            let output_len = filter_selection_avx2_aux(
+                column.as_ptr(),
+                block,
+                &range_simd,
+                output.as_mut_ptr().add(initial_len),
+            );
+            output.set_len(initial_len + output_len);
+        }
+    }
+}
+
+/// `output` must have room for `candidate_ids.len()` ids, which must all be
+/// valid indices of `column`, and lower than `i32::MAX`.
+#[target_feature(enable = "avx2")]
+unsafe fn filter_selection_avx2_aux(
+    column: *const u32,
+    candidate_ids: &[u32],
+    range: &InRange<u32>,
+    output: *mut u32,
+) -> usize {
+    let mut output_tail = output;
+    let mut chunks = candidate_ids.chunks_exact(NUM_LANES);
+    for chunk in &mut chunks {
+        let ids = load_unaligned(chunk.as_ptr() as *const DataType);
+        let vals = _mm256_i32gather_epi32::<4>(column as *const i32, ids);
+        let keeper_bitset = compute_filter_bitset_epi32(vals, &range.range_simd) as u8;
+        store_unaligned(output_tail as *mut DataType, compact(ids, keeper_bitset));
+        output_tail = output_tail.add(keeper_bitset.count_ones() as usize);
+    }
+    for &id in chunks.remainder() {
+        *output_tail = id;
+        if range.contains(*column.add(id as usize)) {
+            output_tail = output_tail.offset(1);
+        }
+    }
+    output_tail.offset_from(output) as usize
+}
+
+// -------------------------------------------------------------------------------------------
+// Values output
+
+/// AVX2 version of [`crate::filter_values`].
+pub fn filter_values<T: Avx2Element>(input: &[T], range: impl RangeBounds<T>, output: &mut Vec<T>) {
+    assert!(is_x86_feature_detected!("avx2"), "AVX2 is not available on this CPU");
+    output.clear();
+    let Some(range) = crate::to_inclusive(range) else {
+        return;
+    };
+    unsafe {
+        let predicate = InRange::new(range);
+        for block in input.chunks(crate::OUTPUT_BLOCK_LEN) {
+            output.reserve(block.len());
+            let initial_len = output.len();
+            let values = output.as_mut_ptr().add(initial_len);
+            let output_len = filter_values_avx2_aux(block.as_ptr(), block.len(), &predicate, values);
+            output.set_len(initial_len + output_len);
+        }
+    }
+}
+
+/// AVX2 version of [`crate::filter_ids_and_values`].
+pub fn filter_ids_and_values<T: Avx2Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    ids: &mut Vec<u32>,
+    values: &mut Vec<T>,
+) {
+    assert!(is_x86_feature_detected!("avx2"), "AVX2 is not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    ids.clear();
+    values.clear();
+    let Some(range) = crate::to_inclusive(range) else {
+        return;
+    };
+    unsafe {
+        let predicate = InRange::new(range);
+        for (block_id, block) in input.chunks(crate::OUTPUT_BLOCK_LEN).enumerate() {
+            ids.reserve(block.len());
+            values.reserve(block.len());
+            let initial_len = ids.len();
+            let output_len = filter_ids_and_values_avx2_aux(
+                block.as_ptr(),
+                block.len(),
+                &predicate,
+                (block_id * crate::OUTPUT_BLOCK_LEN) as u32,
+                ids.as_mut_ptr().add(initial_len),
+                values.as_mut_ptr().add(initial_len),
+            );
+            ids.set_len(initial_len + output_len);
+            values.set_len(initial_len + output_len);
+        }
+    }
+}
+
+/// `values` must have room for `input_len` elements.
+///
+/// `values` may also be `input` itself: the slots written never go past the
+/// elements already read.
+#[target_feature(enable = "avx2")]
+unsafe fn filter_values_avx2_aux<T: Avx2Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+    values: *mut T,
+) -> usize {
+    let num_words = input_len / T::NUM_LANES;
+    let mut values_tail = values;
+    for word_id in 0..num_words {
+        let word = input.add(word_id * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset(word);
+        values_tail = T::write_kept(values_tail, word, keeper_bitset);
+    }
+    scalar_tail(input, input_len, predicate, |_, el, keep| {
+        *values_tail = el;
+        values_tail = values_tail.add(keep as usize);
+    });
+    values_tail.offset_from(values) as usize
+}
+
+/// `ids` and `values` must both have room for `input_len` elements.
+#[target_feature(enable = "avx2")]
+unsafe fn filter_ids_and_values_avx2_aux<T: Avx2Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+    first_id: u32,
+    ids: *mut u32,
+    values: *mut T,
+) -> usize {
+    let num_words = input_len / T::NUM_LANES;
+    let mut ids_tail = ids;
+    let mut values_tail = values;
+    let mut ids_simd = op_add(
+        from_u32x8([0, 1, 2, 3, 4, 5, 6, 7]),
+        _mm256_set1_epi32(first_id as i32),
+    );
+    for word_id in 0..num_words {
+        let word = input.add(word_id * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset(word);
+        ids_tail = write_ids(ids_tail, &mut ids_simd, keeper_bitset, T::NUM_LANES);
+        values_tail = T::write_kept(values_tail, word, keeper_bitset);
+    }
+    scalar_tail(input, input_len, predicate, |id, el, keep| {
+        *ids_tail = first_id + id as u32;
+        *values_tail = el;
+        ids_tail = ids_tail.add(keep as usize);
+        values_tail = values_tail.add(keep as usize);
+    });
+    ids_tail.offset_from(ids) as usize
+}
+
+// -------------------------------------------------------------------------------------------
+// In-place retain
+
+/// AVX2 version of [`crate::retain_in_range`].
+pub fn retain_in_range<T: Avx2Element>(values: &mut Vec<T>, range: impl RangeBounds<T>) {
+    assert!(is_x86_feature_detected!("avx2"), "AVX2 is not available on this CPU");
+    let Some(range) = crate::to_inclusive(range) else {
+        values.clear();
+        return;
+    };
+    unsafe {
+        let predicate = InRange::new(range);
+        let values_ptr = values.as_mut_ptr();
+        let new_len = filter_values_avx2_aux(values_ptr, values.len(), &predicate, values_ptr);
+        values.set_len(new_len);
+    }
+}
+
+// -------------------------------------------------------------------------------------------
+// Partition
+
+/// AVX2 version of [`crate::partition_by_range`].
+pub fn partition_by_range<T: Avx2Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    inside: &mut Vec<u32>,
+    outside: &mut Vec<u32>,
+) {
+    assert!(is_x86_feature_detected!("avx2"), "AVX2 is not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    inside.clear();
+    outside.clear();
+    let Some(range) = crate::to_inclusive(range) else {
+        outside.extend(0..input.len() as u32);
+        return;
+    };
+    unsafe {
+        let predicate = InRange::new(range);
+        for (block_id, block) in input.chunks(crate::OUTPUT_BLOCK_LEN).enumerate() {
+            inside.reserve(block.len());
+            outside.reserve(block.len());
+            let (inside_initial_len, outside_initial_len) = (inside.len(), outside.len());
+            let (inside_len, outside_len) = partition_avx2_aux(
+                block.as_ptr(),
+                block.len(),
+                &predicate,
+                (block_id * crate::OUTPUT_BLOCK_LEN) as u32,
+                inside.as_mut_ptr().add(inside_initial_len),
+                outside.as_mut_ptr().add(outside_initial_len),
+            );
+            inside.set_len(inside_initial_len + inside_len);
+            outside.set_len(outside_initial_len + outside_len);
+        }
+    }
+}
+
+/// `inside` and `outside` must both have room for `input_len` ids.
+#[target_feature(enable = "avx2")]
+unsafe fn partition_avx2_aux<T: Avx2Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+    first_id: u32,
+    inside: *mut u32,
+    outside: *mut u32,
+) -> (usize, usize) {
+    let num_words = input_len / T::NUM_LANES;
+    let mut inside_tail = inside;
+    let mut outside_tail = outside;
+    let mut ids = op_add(
+        from_u32x8([0, 1, 2, 3, 4, 5, 6, 7]),
+        _mm256_set1_epi32(first_id as i32),
+    );
+    for word_id in 0..num_words {
+        let word = input.add(word_id * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset(word);
+        let mut outside_ids = ids;
+        let outside_bitset = !keeper_bitset & word_mask::<T>();
+        outside_tail = write_ids(outside_tail, &mut outside_ids, outside_bitset, T::NUM_LANES);
+        inside_tail = write_ids(inside_tail, &mut ids, keeper_bitset, T::NUM_LANES);
+    }
+    scalar_tail(input, input_len, predicate, |id, _, keep| {
+        *inside_tail = first_id + id as u32;
+        *outside_tail = first_id + id as u32;
+        inside_tail = inside_tail.add(keep as usize);
+        outside_tail = outside_tail.add(!keep as usize);
+    });
+    (
+        inside_tail.offset_from(inside) as usize,
+        outside_tail.offset_from(outside) as usize,
+    )
+}
+
+// -------------------------------------------------------------------------------------------
+// Early exit
+
+/// AVX2 version of [`crate::any_in_range`].
+pub fn any_in_range<T: Avx2Element>(input: &[T], range: impl RangeBounds<T>) -> bool {
+    position_first_in_range(input, range).is_some()
+}
+
+/// AVX2 version of [`crate::position_first_in_range`].
+pub fn position_first_in_range<T: Avx2Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+) -> Option<usize> {
+    assert!(is_x86_feature_detected!("avx2"), "AVX2 is not available on this CPU");
+    let range = crate::to_inclusive(range)?;
+    unsafe { position_first_avx2_aux(input.as_ptr(), input.len(), &InRange::new(range)) }
+}
+
+/// AVX2 version of [`crate::position_last_in_range`].
+pub fn position_last_in_range<T: Avx2Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+) -> Option<usize> {
+    assert!(is_x86_feature_detected!("avx2"), "AVX2 is not available on this CPU");
+    let range = crate::to_inclusive(range)?;
+    unsafe { position_last_avx2_aux(input.as_ptr(), input.len(), &InRange::new(range)) }
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn position_first_avx2_aux<T: Avx2Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+) -> Option<usize> {
+    let num_words = input_len / T::NUM_LANES;
+    for word_id in 0..num_words {
+        let word = input.add(word_id * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset(word);
+        if keeper_bitset != 0 {
+            return Some(word_id * T::NUM_LANES + keeper_bitset.trailing_zeros() as usize);
+        }
+    }
+    (num_words * T::NUM_LANES..input_len).find(|&id| predicate.contains(*input.add(id)))
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn position_last_avx2_aux<T: Avx2Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+) -> Option<usize> {
+    let num_words = input_len / T::NUM_LANES;
+    let tail_position =
+        (num_words * T::NUM_LANES..input_len).rfind(|&id| predicate.contains(*input.add(id)));
+    if tail_position.is_some() {
+        return tail_position;
+    }
+    for word_id in (0..num_words).rev() {
+        let word = input.add(word_id * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset(word);
+        if keeper_bitset != 0 {
+            return Some(word_id * T::NUM_LANES + (31 - keeper_bitset.leading_zeros()) as usize);
+        }
+    }
+    None
+}
+
+// -------------------------------------------------------------------------------------------
+// Limit
+
+/// AVX2 version of [`crate::filter_vec_limit`].
+pub fn filter_vec_limit<T: Avx2Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    skip: usize,
+    limit: usize,
+    output: &mut Vec<u32>,
+) {
+    assert!(is_x86_feature_detected!("avx2"), "AVX2 is not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    output.clear();
+    let Some(range) = crate::to_inclusive(range) else {
+        return;
+    };
+    let limit = limit.min(input.len());
+    output.reserve(limit);
+    unsafe {
+        let predicate = InRange::new(range);
+        let output_len = filter_limit_avx2_aux(
+            input.as_ptr(),
+            input.len(),
+            &predicate,
+            skip,
+            limit,
+            output.as_mut_ptr(),
+        );
+        output.set_len(output_len);
+    }
+}
+
+/// `output` must have room for `limit` ids.
+#[target_feature(enable = "avx2")]
+unsafe fn filter_limit_avx2_aux<T: Avx2Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+    mut skip: usize,
+    limit: usize,
+    output: *mut u32,
+) -> usize {
+    let num_words = input_len / T::NUM_LANES;
+    let mut num_written = 0;
+    let mut ids = from_u32x8([0, 1, 2, 3, 4, 5, 6, 7]);
+    // Once there is no room left for a full word of ids in `output`, they are
+    // compacted in this buffer first.
+    let mut buffer = [0u32; 32];
+    for word_id in 0..num_words {
+        if num_written == limit {
+            return num_written;
+        }
+        let word = input.add(word_id * T::NUM_LANES);
+        let mut keeper_bitset = predicate.compute_filter_bitset(word);
+        if skip > 0 {
+            let num_skipped = skip.min(keeper_bitset.count_ones() as usize);
+            keeper_bitset ^= crate::lowest_set_bits(keeper_bitset as u64, num_skipped) as u32;
+            skip -= num_skipped;
+        }
+        let room = limit - num_written;
+        let output_tail = output.add(num_written);
+        if room >= T::NUM_LANES {
+            let new_tail = write_ids(output_tail, &mut ids, keeper_bitset, T::NUM_LANES);
+            num_written += new_tail.offset_from(output_tail) as usize;
+        } else {
+            keeper_bitset = crate::lowest_set_bits(keeper_bitset as u64, room) as u32;
+            let buffer_tail = write_ids(buffer.as_mut_ptr(), &mut ids, keeper_bitset, T::NUM_LANES);
+            let num_ids = buffer_tail.offset_from(buffer.as_ptr()) as usize;
+            std::ptr::copy_nonoverlapping(buffer.as_ptr(), output_tail, num_ids);
+            num_written += num_ids;
+        }
+    }
+    scalar_tail(input, input_len, predicate, |id, _, keep| {
+        if !keep || num_written == limit {
+            return;
+        }
+        if skip > 0 {
+            skip -= 1;
+        } else {
+            *output.add(num_written) = id as u32;
+            num_written += 1;
+        }
+    });
+    num_written
+}
+
+// -------------------------------------------------------------------------------------------
+// Slice output
+
+/// AVX2 version of [`crate::filter_vec_into_slice`].
+pub fn filter_vec_into_slice<T: Avx2Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    output: &mut [MaybeUninit<u32>],
+) -> usize {
+    assert!(is_x86_feature_detected!("avx2"), "AVX2 is not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    crate::assert_output_fits(input.len(), output.len());
+    let Some(range) = crate::to_inclusive(range) else {
+        return 0;
+    };
+    unsafe {
+        let predicate = InRange::new(range);
+        filter_vec_avx2_aux(input.as_ptr(), input.len(), &predicate, 0, output.as_mut_ptr() as *mut u32)
+    }
+}
+
+// -------------------------------------------------------------------------------------------
+// Visitor
+
+/// AVX2 version of [`crate::filter_vec_visit`].
+pub fn filter_vec_visit<T: Avx2Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    mut visit: impl FnMut(&[u32]),
+) {
+    assert!(is_x86_feature_detected!("avx2"), "AVX2 is not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    let Some(range) = crate::to_inclusive(range) else {
+        return;
+    };
+    let mut ids = [0u32; crate::VISIT_BLOCK_LEN];
+    unsafe {
+        let predicate = InRange::new(range);
+        for (block_id, block) in input.chunks(crate::VISIT_BLOCK_LEN).enumerate() {
+            let num_ids = filter_vec_avx2_aux(
+                block.as_ptr(),
+                block.len(),
+                &predicate,
+                (block_id * crate::VISIT_BLOCK_LEN) as u32,
+                ids.as_mut_ptr(),
+            );
+            if num_ids > 0 {
+                visit(&ids[..num_ids]);
+            }
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------
+// Predicates
+
+/// Test run by the kernels on every element.
+trait Predicate<T: Avx2Element> {
+    /// Loads the word starting at `word`, and returns the bitset of its
+    /// elements that pass the test.
+    unsafe fn compute_filter_bitset(&self, word: *const T) -> u32;
+
+    /// Scalar version of the test, used on the tail of the input.
+    fn contains(&self, el: T) -> bool;
+}
+
+/// Calls `emit` on each element of the scalar tail of `input`, the last
+/// `input_len % T::NUM_LANES` elements that do not fill a whole word, with its
+/// position, its value and whether it passes `predicate`.
+#[inline]
+unsafe fn scalar_tail<T: Avx2Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+    mut emit: impl FnMut(usize, T, bool),
+) {
+    for id in input_len - input_len % T::NUM_LANES..input_len {
+        let el = *input.add(id);
+        emit(id, el, predicate.contains(el));
+    }
+}
+
+struct InRange<T: Avx2Element> {
+    range: RangeInclusive<T>,
+    range_simd: T::SimdRange,
+}
+
+impl<T: Avx2Element> InRange<T> {
+    #[target_feature(enable = "avx2")]
+    unsafe fn new(range: RangeInclusive<T>) -> Self {
+        let range_simd = T::simd_range(&range);
+        InRange { range, range_simd }
+    }
+}
+
+impl<T: Avx2Element> Predicate<T> for InRange<T> {
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn compute_filter_bitset(&self, word: *const T) -> u32 {
+        T::compute_filter_bitset(word, &self.range_simd)
+    }
+
+    #[inline]
+    fn contains(&self, el: T) -> bool {
+        self.range.contains(&el)
+    }
+}
+
+/// Union of several ranges: the bitsets of the ranges are OR-ed together.
+struct AnyInRange<T: Avx2Element> {
+    ranges: Vec<InRange<T>>,
+}
+
+impl<T: Avx2Element> AnyInRange<T> {
+    #[target_feature(enable = "avx2")]
+    unsafe fn new(ranges: Vec<RangeInclusive<T>>) -> Self {
+        let ranges = ranges.into_iter().map(|range| InRange::new(range)).collect();
+        AnyInRange { ranges }
+    }
+}
+
+impl<T: Avx2Element> Predicate<T> for AnyInRange<T> {
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn compute_filter_bitset(&self, word: *const T) -> u32 {
+        let mut bitset = 0;
+        for range in &self.ranges {
+            bitset |= range.compute_filter_bitset(word);
+        }
+        bitset
+    }
+
+    #[inline]
+    fn contains(&self, el: T) -> bool {
+        self.ranges.iter().any(|range| range.contains(el))
+    }
+}
+
+/// Complement of [`InRange`]. NaN is outside of every range.
+struct Outside<T: Avx2Element>(InRange<T>);
+
+impl<T: Avx2Element> Predicate<T> for Outside<T> {
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn compute_filter_bitset(&self, word: *const T) -> u32 {
+        !self.0.compute_filter_bitset(word) & word_mask::<T>()
+    }
+
+    #[inline]
+    fn contains(&self, el: T) -> bool {
+        !self.0.contains(el)
+    }
+}
+
+struct Equal<T: Avx2Element> {
+    value: T,
+    value_simd: T::SimdValue,
+}
+
+impl<T: Avx2Element> Equal<T> {
+    #[target_feature(enable = "avx2")]
+    unsafe fn new(value: T) -> Self {
+        Equal { value, value_simd: T::splat(value) }
+    }
+}
+
+impl<T: Avx2Element> Predicate<T> for Equal<T> {
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn compute_filter_bitset(&self, word: *const T) -> u32 {
+        T::compute_eq_bitset(word, &self.value_simd)
+    }
+
+    #[inline]
+    fn contains(&self, el: T) -> bool {
+        el == self.value
+    }
+}
+
+/// Complement of [`Equal`]. Like `!=`, it accepts NaN.
+struct NotEqual<T: Avx2Element>(Equal<T>);
+
+impl<T: Avx2Element> Predicate<T> for NotEqual<T> {
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn compute_filter_bitset(&self, word: *const T) -> u32 {
+        !self.0.compute_filter_bitset(word) & word_mask::<T>()
+    }
+
+    #[inline]
+    fn contains(&self, el: T) -> bool {
+        !self.0.contains(el)
+    }
+}
+
+/// Compares every element against each value of a tiny set.
+struct AnyOf<'a> {
+    values: &'a [u32],
+    values_simd: Vec<DataType>,
+}
+
+impl<'a> AnyOf<'a> {
+    #[target_feature(enable = "avx2")]
+    unsafe fn new(values: &'a [u32]) -> Self {
+        let values_simd = values
+            .iter()
+            .map(|&value| _mm256_set1_epi32(value as i32))
+            .collect();
+        AnyOf { values, values_simd }
+    }
+}
+
+impl Predicate<u32> for AnyOf<'_> {
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn compute_filter_bitset(&self, word: *const u32) -> u32 {
+        let val = load_unaligned(word as *const DataType);
+        let mut matches = _mm256_setzero_si256();
+        for &value in &self.values_simd {
+            matches = op_or(matches, _mm256_cmpeq_epi32(val, value));
+        }
+        _mm256_movemask_ps(_mm256_castsi256_ps(matches)) as u32
+    }
+
+    #[inline]
+    fn contains(&self, el: u32) -> bool {
+        self.values.contains(&el)
+    }
+}
+
+/// Branchless binary search of every lane in a sorted set, with one gather per step.
+struct SortedSearch<'a> {
+    set: &'a U32Set,
+    values: &'a [u32],
+}
+
+impl Predicate<u32> for SortedSearch<'_> {
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn compute_filter_bitset(&self, word: *const u32) -> u32 {
+        const SIGN_BIAS: DataType = from_u32x8([SIGN_BIT_32; NUM_LANES]);
+        let values = self.values.as_ptr() as *const i32;
+        let val = load_unaligned(word as *const DataType);
+        let val_biased = op_xor(val, SIGN_BIAS);
+        // `base` ends up on the last value lower or equal to `val`, if any.
+        let mut base = _mm256_setzero_si256();
+        let mut len = self.values.len();
+        while len > 1 {
+            let half = len / 2;
+            let probe = _mm256_i32gather_epi32::<4>(values, op_add(base, _mm256_set1_epi32(half as i32)));
+            let too_high = _mm256_cmpgt_epi32(op_xor(probe, SIGN_BIAS), val_biased);
+            base = op_add(base, _mm256_andnot_si256(too_high, _mm256_set1_epi32(half as i32)));
+            len -= half;
+        }
+        let found = _mm256_i32gather_epi32::<4>(values, base);
+        _mm256_movemask_ps(_mm256_castsi256_ps(_mm256_cmpeq_epi32(found, val))) as u32
+    }
+
+    #[inline]
+    fn contains(&self, el: u32) -> bool {
+        self.set.contains(el)
+    }
+}
+
+/// Looks every lane up in the bitmap of a dense set, with a masked gather.
+struct BitmapLookup<'a> {
+    set: &'a U32Set,
+    words: &'a [u32],
+    min: DataType,
+    span_biased: DataType,
+}
+
+impl<'a> BitmapLookup<'a> {
+    #[target_feature(enable = "avx2")]
+    unsafe fn new(set: &'a U32Set, min: u32, span: u32, words: &'a [u32]) -> Self {
+        BitmapLookup {
+            set,
+            words,
+            min: _mm256_set1_epi32(min as i32),
+            span_biased: _mm256_set1_epi32((span ^ SIGN_BIT_32) as i32),
+        }
+    }
+}
+
+impl Predicate<u32> for BitmapLookup<'_> {
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn compute_filter_bitset(&self, word: *const u32) -> u32 {
+        const SIGN_BIAS: DataType = from_u32x8([SIGN_BIT_32; NUM_LANES]);
+        const BIT_MASK: DataType = from_u32x8([31; NUM_LANES]);
+        const ONE: DataType = from_u32x8([1; NUM_LANES]);
+        let val = load_unaligned(word as *const DataType);
+        let offset = _mm256_sub_epi32(val, self.min);
+        // Lanes outside of the bitmap are not gathered, and read as 0.
+        let in_domain = _mm256_cmpgt_epi32(self.span_biased, op_xor(offset, SIGN_BIAS));
+        let bitmap_word = _mm256_mask_i32gather_epi32::<4>(
+            _mm256_setzero_si256(),
+            self.words.as_ptr() as *const i32,
+            _mm256_srli_epi32::<5>(offset),
+            in_domain,
+        );
+        let bit = _mm256_and_si256(_mm256_srlv_epi32(bitmap_word, _mm256_and_si256(offset, BIT_MASK)), ONE);
+        _mm256_movemask_ps(_mm256_castsi256_ps(_mm256_cmpeq_epi32(bit, ONE))) as u32
+    }
+
+    #[inline]
+    fn contains(&self, el: u32) -> bool {
+        self.set.contains(el)
+    }
+}
+
+/// Bitset with one bit set per element of a word.
 #[inline]
-unsafe fn compute_filter_bitset(val: __m256i, range: std::ops::RangeInclusive<__m256i>) -> u8 {
-    let too_low = op_greater(*range.start(), val);
-    let too_high = op_greater(val,*range.end());
-    let inside = op_or(too_low, too_high);
-    255 - std::arch::x86_64::_mm256_movemask_ps(std::mem::transmute::<DataType, __m256>(inside))
-        as u8
+fn word_mask<T: Avx2Element>() -> u32 {
+    (u64::MAX >> (64 - T::NUM_LANES)) as u32
+}
+
+// -------------------------------------------------------------------------------------------
+// Element types
+
+/// Integer range `start..=end`, tested as `x - start <= end - start` in unsigned
+/// arithmetic: a subtraction and a single comparison, whatever the signedness.
+///
+/// Both `start` and the width are stored with their sign bit flipped.
+#[doc(hidden)]
+#[derive(Clone, Copy)]
+pub struct OffsetRange {
+    start: DataType,
+    width: DataType,
+}
+
+/// Implements [`Avx2Element`] for the integer type `$t`, whose unsigned counterpart is
+/// `$u`, with the intrinsics of its lane width.
+macro_rules! impl_avx2_element_for_int {
+    (
+        $t:ty, $u:ty, $num_lanes:expr, $sign_bit:expr, $set1:ident as $lane:ty,
+        $compute_filter_bitset:ident, $compute_eq_bitset:ident, $write_kept:ident
+    ) => {
+        impl Avx2Element for $t {
+            const NUM_LANES: usize = $num_lanes;
+
+            type SimdRange = OffsetRange;
+
+            #[inline]
+            #[target_feature(enable = "avx2")]
+            unsafe fn simd_range(range: &RangeInclusive<$t>) -> Self::SimdRange {
+                // AVX2 only has signed comparisons: flipping the sign bit of both
+                // `x - start` and the width maps the unsigned order onto the signed
+                // order. `(x - start) ^ sign_bit` is `x - (start ^ sign_bit)`, so the
+                // flip of `x - start` comes for free with the subtraction.
+                let width = range.end().wrapping_sub(*range.start()) as $u;
+                OffsetRange {
+                    start: $set1(((*range.start() as $u) ^ $sign_bit) as $lane),
+                    width: $set1((width ^ $sign_bit) as $lane),
+                }
+            }
+
+            #[inline]
+            #[target_feature(enable = "avx2")]
+            unsafe fn compute_filter_bitset(word: *const $t, range: &Self::SimdRange) -> u32 {
+                $compute_filter_bitset(load_unaligned(word as *const DataType), range)
+            }
+
+            type SimdValue = DataType;
+
+            #[inline]
+            #[target_feature(enable = "avx2")]
+            unsafe fn splat(value: $t) -> Self::SimdValue {
+                $set1(value as $lane)
+            }
+
+            #[inline]
+            #[target_feature(enable = "avx2")]
+            unsafe fn compute_eq_bitset(word: *const $t, value: &Self::SimdValue) -> u32 {
+                $compute_eq_bitset(load_unaligned(word as *const DataType), value)
+            }
+
+            #[inline]
+            #[target_feature(enable = "avx2")]
+            unsafe fn write_kept(output_tail: *mut $t, word: *const $t, keeper_bitset: u32) -> *mut $t {
+                $write_kept(output_tail, word, keeper_bitset)
+            }
+        }
+    };
+}
+
+impl_avx2_element_for_int!(
+    u8, u8, 32, SIGN_BIT_8, _mm256_set1_epi8 as i8,
+    compute_filter_bitset_epi8, compute_eq_bitset_epi8, write_kept_scalar
+);
+impl_avx2_element_for_int!(
+    i8, u8, 32, SIGN_BIT_8, _mm256_set1_epi8 as i8,
+    compute_filter_bitset_epi8, compute_eq_bitset_epi8, write_kept_scalar
+);
+impl_avx2_element_for_int!(
+    u16, u16, 16, SIGN_BIT_16, _mm256_set1_epi16 as i16,
+    compute_filter_bitset_epi16, compute_eq_bitset_epi16, write_kept_scalar
+);
+impl_avx2_element_for_int!(
+    i16, u16, 16, SIGN_BIT_16, _mm256_set1_epi16 as i16,
+    compute_filter_bitset_epi16, compute_eq_bitset_epi16, write_kept_scalar
+);
+impl_avx2_element_for_int!(
+    u32, u32, 8, SIGN_BIT_32, _mm256_set1_epi32 as i32,
+    compute_filter_bitset_epi32, compute_eq_bitset_epi32, write_kept_epi32
+);
+impl_avx2_element_for_int!(
+    i32, u32, 8, SIGN_BIT_32, _mm256_set1_epi32 as i32,
+    compute_filter_bitset_epi32, compute_eq_bitset_epi32, write_kept_epi32
+);
+impl_avx2_element_for_int!(
+    u64, u64, 4, SIGN_BIT_64, _mm256_set1_epi64x as i64,
+    compute_filter_bitset_epi64, compute_eq_bitset_epi64, write_kept_epi64
+);
+impl_avx2_element_for_int!(
+    i64, u64, 4, SIGN_BIT_64, _mm256_set1_epi64x as i64,
+    compute_filter_bitset_epi64, compute_eq_bitset_epi64, write_kept_epi64
+);
+
+impl Avx2Element for f32 {
+    const NUM_LANES: usize = 8;
+
+    type SimdRange = RangeInclusive<__m256>;
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn simd_range(range: &RangeInclusive<f32>) -> Self::SimdRange {
+        _mm256_set1_ps(*range.start())..=_mm256_set1_ps(*range.end())
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn compute_filter_bitset(word: *const f32, range: &Self::SimdRange) -> u32 {
+        // Ordered comparisons are false for NaN, which is never in range.
+        let val = _mm256_loadu_ps(word);
+        let low = _mm256_cmp_ps::<_CMP_LE_OQ>(*range.start(), val);
+        let high = _mm256_cmp_ps::<_CMP_LE_OQ>(val, *range.end());
+        _mm256_movemask_ps(_mm256_and_ps(low, high)) as u32
+    }
+
+    type SimdValue = __m256;
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn splat(value: f32) -> Self::SimdValue {
+        _mm256_set1_ps(value)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn compute_eq_bitset(word: *const f32, value: &Self::SimdValue) -> u32 {
+        let eq = _mm256_cmp_ps::<_CMP_EQ_OQ>(_mm256_loadu_ps(word), *value);
+        _mm256_movemask_ps(eq) as u32
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn write_kept(output_tail: *mut f32, word: *const f32, keeper_bitset: u32) -> *mut f32 {
+        write_kept_epi32(output_tail, word, keeper_bitset)
+    }
+}
+
+impl Avx2Element for f64 {
+    const NUM_LANES: usize = 4;
+
+    type SimdRange = RangeInclusive<__m256d>;
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn simd_range(range: &RangeInclusive<f64>) -> Self::SimdRange {
+        _mm256_set1_pd(*range.start())..=_mm256_set1_pd(*range.end())
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn compute_filter_bitset(word: *const f64, range: &Self::SimdRange) -> u32 {
+        let val = _mm256_loadu_pd(word);
+        let low = _mm256_cmp_pd::<_CMP_LE_OQ>(*range.start(), val);
+        let high = _mm256_cmp_pd::<_CMP_LE_OQ>(val, *range.end());
+        _mm256_movemask_pd(_mm256_and_pd(low, high)) as u32
+    }
+
+    type SimdValue = __m256d;
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn splat(value: f64) -> Self::SimdValue {
+        _mm256_set1_pd(value)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn compute_eq_bitset(word: *const f64, value: &Self::SimdValue) -> u32 {
+        let eq = _mm256_cmp_pd::<_CMP_EQ_OQ>(_mm256_loadu_pd(word), *value);
+        _mm256_movemask_pd(eq) as u32
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn write_kept(output_tail: *mut f64, word: *const f64, keeper_bitset: u32) -> *mut f64 {
+        write_kept_epi64(output_tail, word, keeper_bitset)
+    }
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn compute_filter_bitset_epi8(val: DataType, range: &OffsetRange) -> u32 {
+    let outside = _mm256_cmpgt_epi8(_mm256_sub_epi8(val, range.start), range.width);
+    !(_mm256_movemask_epi8(outside) as u32)
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn compute_filter_bitset_epi16(val: DataType, range: &OffsetRange) -> u32 {
+    let outside = _mm256_cmpgt_epi16(_mm256_sub_epi16(val, range.start), range.width);
+    // Saturating the 16-bit lanes to 8 bits keeps one byte per element, in order.
+    let outside_bytes = _mm_packs_epi16(
+        _mm256_castsi256_si128(outside),
+        _mm256_extracti128_si256::<1>(outside),
+    );
+    0xFFFF - _mm_movemask_epi8(outside_bytes) as u32
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn compute_filter_bitset_epi32(val: DataType, range: &OffsetRange) -> u32 {
+    let outside = _mm256_cmpgt_epi32(_mm256_sub_epi32(val, range.start), range.width);
+    255 - _mm256_movemask_ps(_mm256_castsi256_ps(outside)) as u32
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn compute_filter_bitset_epi64(val: DataType, range: &OffsetRange) -> u32 {
+    let outside = _mm256_cmpgt_epi64(_mm256_sub_epi64(val, range.start), range.width);
+    15 - _mm256_movemask_pd(_mm256_castsi256_pd(outside)) as u32
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn compute_eq_bitset_epi8(val: DataType, value: &DataType) -> u32 {
+    _mm256_movemask_epi8(_mm256_cmpeq_epi8(val, *value)) as u32
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn compute_eq_bitset_epi16(val: DataType, value: &DataType) -> u32 {
+    let eq = _mm256_cmpeq_epi16(val, *value);
+    let eq_bytes = _mm_packs_epi16(_mm256_castsi256_si128(eq), _mm256_extracti128_si256::<1>(eq));
+    _mm_movemask_epi8(eq_bytes) as u32
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn compute_eq_bitset_epi32(val: DataType, value: &DataType) -> u32 {
+    _mm256_movemask_ps(_mm256_castsi256_ps(_mm256_cmpeq_epi32(val, *value))) as u32
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn compute_eq_bitset_epi64(val: DataType, value: &DataType) -> u32 {
+    _mm256_movemask_pd(_mm256_castsi256_pd(_mm256_cmpeq_epi64(val, *value))) as u32
+}
+
+/// `T` must be a 32-bit type.
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn write_kept_epi32<T>(output_tail: *mut T, word: *const T, keeper_bitset: u32) -> *mut T {
+    let keeper = keeper_bitset as u8;
+    let val = load_unaligned(word as *const DataType);
+    store_unaligned(output_tail as *mut DataType, compact(val, keeper));
+    output_tail.add(keeper.count_ones() as usize)
+}
+
+/// `T` must be a 64-bit type.
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn write_kept_epi64<T>(output_tail: *mut T, word: *const T, keeper_bitset: u32) -> *mut T {
+    let val = load_unaligned(word as *const DataType);
+    let vperm_mask = MASK_TO_PERMUTATION_64[keeper_bitset as usize];
+    store_unaligned(output_tail as *mut DataType, _mm256_permutevar8x32_epi32(val, vperm_mask));
+    output_tail.add(keeper_bitset.count_ones() as usize)
+}
+
+/// There is no byte or 16-bit lane permutation in AVX2: elements are copied one
+/// by one, without branches.
+#[inline]
+unsafe fn write_kept_scalar<T: Avx2Element>(
+    mut output_tail: *mut T,
+    word: *const T,
+    keeper_bitset: u32,
+) -> *mut T {
+    for i in 0..T::NUM_LANES {
+        *output_tail = *word.add(i);
+        output_tail = output_tail.add((keeper_bitset >> i) as usize & 1);
+    }
+    output_tail
 }
 
 union U8x32 {
@@ -81,6 +1419,55 @@ const fn from_u32x8(vals: [u32; NUM_LANES]) -> DataType {
     unsafe { U8x32 { vals }.vector }
 }
 
+union U8x16 {
+    vector: __m128i,
+    vals: [u32; NUM_LANES / 2],
+}
+
+const fn from_u32x4(vals: [u32; NUM_LANES / 2]) -> __m128i {
+    unsafe { U8x16 { vals }.vector }
+}
+
+const MASK_TO_PERMUTATION_4: [__m128i; 16] = [
+    from_u32x4([0, 0, 0, 0]),
+    from_u32x4([0, 0, 0, 0]),
+    from_u32x4([1, 0, 0, 0]),
+    from_u32x4([0, 1, 0, 0]),
+    from_u32x4([2, 0, 0, 0]),
+    from_u32x4([0, 2, 0, 0]),
+    from_u32x4([1, 2, 0, 0]),
+    from_u32x4([0, 1, 2, 0]),
+    from_u32x4([3, 0, 0, 0]),
+    from_u32x4([0, 3, 0, 0]),
+    from_u32x4([1, 3, 0, 0]),
+    from_u32x4([0, 1, 3, 0]),
+    from_u32x4([2, 3, 0, 0]),
+    from_u32x4([0, 2, 3, 0]),
+    from_u32x4([1, 2, 3, 0]),
+    from_u32x4([0, 1, 2, 3]),
+];
+
+/// Same as `MASK_TO_PERMUTATION`, for 4 lanes of 64 bits: each 64-bit lane moves
+/// as a pair of 32-bit lanes.
+const MASK_TO_PERMUTATION_64: [DataType; 16] = [
+    from_u32x8([0, 0, 0, 0, 0, 0, 0, 0]),
+    from_u32x8([0, 1, 0, 0, 0, 0, 0, 0]),
+    from_u32x8([2, 3, 0, 0, 0, 0, 0, 0]),
+    from_u32x8([0, 1, 2, 3, 0, 0, 0, 0]),
+    from_u32x8([4, 5, 0, 0, 0, 0, 0, 0]),
+    from_u32x8([0, 1, 4, 5, 0, 0, 0, 0]),
+    from_u32x8([2, 3, 4, 5, 0, 0, 0, 0]),
+    from_u32x8([0, 1, 2, 3, 4, 5, 0, 0]),
+    from_u32x8([6, 7, 0, 0, 0, 0, 0, 0]),
+    from_u32x8([0, 1, 6, 7, 0, 0, 0, 0]),
+    from_u32x8([2, 3, 6, 7, 0, 0, 0, 0]),
+    from_u32x8([0, 1, 2, 3, 6, 7, 0, 0]),
+    from_u32x8([4, 5, 6, 7, 0, 0, 0, 0]),
+    from_u32x8([0, 1, 4, 5, 6, 7, 0, 0]),
+    from_u32x8([2, 3, 4, 5, 6, 7, 0, 0]),
+    from_u32x8([0, 1, 2, 3, 4, 5, 6, 7]),
+];
+
 const MASK_TO_PERMUTATION: [DataType; 256] = [
     from_u32x8([0, 0, 0, 0, 0, 0, 0, 0]),
     from_u32x8([0, 0, 0, 0, 0, 0, 0, 0]),
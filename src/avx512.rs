@@ -1,63 +1,1886 @@
+//! AVX-512 kernels.
+//!
+//! Each function is the AVX-512 version of the function of the same name at the crate
+//! root, where its behavior is documented. They panic if the CPU does not support
+//! AVX-512F and AVX-512BW.
+
 use std::arch::x86_64::__m512i as DataType;
 use std::arch::x86_64::_mm512_add_epi32 as op_add;
-use std::arch::x86_64::_mm512_cmple_epi32_mask as op_less_or_equal;
-use std::arch::x86_64::_mm512_loadu_epi32 as load_unaligned;
-use std::arch::x86_64::_mm512_set1_epi32 as set1;
 use std::arch::x86_64::_mm512_mask_compressstoreu_epi32 as compress;
 use std::arch::x86_64::*;
-use std::ops::RangeInclusive;
+use std::mem::MaybeUninit;
+use std::ops::{RangeBounds, RangeInclusive};
+
+use crate::private::Sealed;
+use crate::set::U32Set;
 
+/// Number of ids compacted by a single compress store.
 const NUM_LANES: usize = 16;
 
-pub fn filter_vec(input: &[u32], range: RangeInclusive<u32>, output: &mut Vec<u32>) {
-    assert_eq!(input.len() % NUM_LANES, 0);
-    // We restrict the accepted bondary, because unsigned integers & SIMD don't
-    // play well.
-    let accepted_range = 0u32..(i32::MAX as u32);
-    assert!(accepted_range.contains(range.start()));
-    assert!(accepted_range.contains(range.end()));
+/// Returns true if the running CPU supports the instructions used by this
+/// module: AVX-512F, and AVX-512BW for the 8-bit and 16-bit elements.
+pub(crate) fn is_available() -> bool {
+    is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw")
+}
+
+/// Element types with AVX-512 kernels.
+///
+/// This trait is sealed, it is implemented for every [`FilterElement`](crate::FilterElement).
+pub trait Avx512Element: Sealed + Copy + PartialOrd {
+    /// Number of elements in a 512-bit word.
+    const NUM_LANES: usize;
+
+    #[doc(hidden)]
+    type SimdRange;
+
+    /// `range` must not be empty.
+    #[doc(hidden)]
+    unsafe fn simd_range(range: &RangeInclusive<Self>) -> Self::SimdRange;
+
+    /// Loads the word starting at `word`, and returns the bitset of its
+    /// elements that belong to `range`.
+    #[doc(hidden)]
+    unsafe fn compute_filter_bitset(word: *const Self, range: &Self::SimdRange) -> u64;
+
+    /// Same as `compute_filter_bitset`, but only loads the lanes set in
+    /// `load_mask`. The other lanes are never part of the result.
+    #[doc(hidden)]
+    unsafe fn compute_filter_bitset_masked(
+        word: *const Self,
+        load_mask: u64,
+        range: &Self::SimdRange,
+    ) -> u64;
+
+    #[doc(hidden)]
+    type SimdValue;
+
+    #[doc(hidden)]
+    unsafe fn splat(value: Self) -> Self::SimdValue;
+
+    /// Loads the word starting at `word`, and returns the bitset of its
+    /// elements equal to `value`.
+    #[doc(hidden)]
+    unsafe fn compute_eq_bitset(word: *const Self, value: &Self::SimdValue) -> u64;
+
+    /// Same as `compute_eq_bitset`, but only loads the lanes set in `load_mask`.
+    #[doc(hidden)]
+    unsafe fn compute_eq_bitset_masked(
+        word: *const Self,
+        load_mask: u64,
+        value: &Self::SimdValue,
+    ) -> u64;
+
+    /// Writes at `output_end` the elements of the word starting at `word` that
+    /// are set in `keeper_bitset`, and returns the new end.
+    ///
+    /// Only the elements kept are read and written.
+    #[doc(hidden)]
+    unsafe fn write_kept(output_end: *mut Self, word: *const Self, keeper_bitset: u64) -> *mut Self;
+}
+
+/// AVX-512 version of [`crate::filter_vec`].
+pub fn filter_vec<T: Avx512Element>(input: &[T], range: impl RangeBounds<T>, output: &mut Vec<u32>) {
+    filter_vec_with_offset(input, range, 0, output);
+}
+
+/// AVX-512 version of [`crate::filter_vec_with_offset`].
+pub fn filter_vec_with_offset<T: Avx512Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    first_id: u32,
+    output: &mut Vec<u32>,
+) {
+    output.clear();
+    filter_vec_append(input, range, first_id, output);
+}
+
+/// AVX-512 version of [`crate::filter_vec_append`].
+pub fn filter_vec_append<T: Avx512Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    first_id: u32,
+    output: &mut Vec<u32>,
+) {
+    assert!(is_available(), "AVX-512F and AVX-512BW are not available on this CPU");
+    crate::assert_ids_fit(first_id, input.len());
+    let Some(range) = crate::to_inclusive(range) else {
+        return;
+    };
+    unsafe {
+        append_in_range(input, range, first_id, output);
+    }
+}
+
+/// Appends to `output` the ids of the elements of `input` that belong to `range`.
+///
+/// # Safety
+///
+/// The CPU must support AVX-512F and AVX-512BW. `range` must not be empty, and the ids of `input` must
+/// fit in a `u32`.
+pub(crate) unsafe fn append_in_range<T: Avx512Element>(
+    input: &[T],
+    range: RangeInclusive<T>,
+    first_id: u32,
+    output: &mut Vec<u32>,
+) {
+    append_ids(input, &InRange::new(range), first_id, output);
+}
+
+/// Appends to `output` the ids of the elements of `input` that pass `predicate`.
+///
+/// # Safety
+///
+/// The CPU must support AVX-512F and AVX-512BW.
+unsafe fn append_ids<T: Avx512Element, P: Predicate<T>>(
+    input: &[T],
+    predicate: &P,
+    first_id: u32,
+    output: &mut Vec<u32>,
+) {
+    for (block_id, block) in input.chunks(crate::OUTPUT_BLOCK_LEN).enumerate() {
+        output.reserve(block.len());
+        let initial_len = output.len();
+        let output_len = filter_ids_aux(
+            block.as_ptr(),
+            block.len(),
+            predicate,
+            first_id + (block_id * crate::OUTPUT_BLOCK_LEN) as u32,
+            output.as_mut_ptr().add(initial_len),
+        );
+        output.set_len(initial_len + output_len);
+    }
+}
+
+/// # Safety
+///
+/// The CPU must support AVX-512F and AVX-512BW, `input` must point to `input_len`
+/// readable values and `output` must have room for `input_len` ids. The ids
+/// `first_id..first_id + input_len` must fit in a `u32`.
+#[target_feature(enable = "avx512f,avx512bw")]
+pub unsafe fn filter_vec_aux<T: Avx512Element>(
+    input: *const T,
+    input_len: usize,
+    range: RangeInclusive<T>,
+    first_id: u32,
+    output: *mut u32,
+) -> usize {
+    filter_ids_aux(input, input_len, &InRange::new(range), first_id, output)
+}
+
+#[target_feature(enable = "avx512f,avx512bw")]
+unsafe fn filter_ids_aux<T: Avx512Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+    first_id: u32,
+    output: *mut u32,
+) -> usize {
+    let num_words = input_len / T::NUM_LANES;
+    let mut output_end = output;
+    let mut ids = op_add(
+        from_u32x16([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]),
+        _mm512_set1_epi32(first_id as i32),
+    );
+    for word_id in 0..num_words {
+        let word = input.add(word_id * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset(word);
+        output_end = write_ids(output_end, &mut ids, keeper_bitset, T::NUM_LANES);
+    }
+    let tail_len = input_len % T::NUM_LANES;
+    if tail_len > 0 {
+        // Masked-out lanes are neither read nor emitted.
+        let tail_mask = (1u64 << tail_len) - 1;
+        let word = input.add(num_words * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset_masked(word, tail_mask);
+        output_end = write_ids(output_end, &mut ids, keeper_bitset, T::NUM_LANES);
+    }
+    output_end.offset_from(output) as usize
+}
+
+/// Writes at `output_end` the ids of the `num_lanes` elements following `ids`
+/// that are set in `keeper_bitset`, and returns the new end.
+///
+/// Only the ids kept are written.
+#[inline]
+#[target_feature(enable = "avx512f,avx512bw")]
+unsafe fn write_ids(
+    mut output_end: *mut u32,
+    ids: &mut DataType,
+    mut keeper_bitset: u64,
+    num_lanes: usize,
+) -> *mut u32 {
+    const SHIFT: DataType = from_u32x16([NUM_LANES as u32; NUM_LANES]);
+    const HALF_SHIFT: DataType = from_u32x16([NUM_LANES as u32 / 2; NUM_LANES]);
+    if num_lanes == NUM_LANES / 2 {
+        compress(output_end as *mut i32, keeper_bitset as u16, *ids);
+        *ids = op_add(*ids, HALF_SHIFT);
+        return output_end.add(keeper_bitset.count_ones() as usize);
+    }
+    for _ in 0..num_lanes / NUM_LANES {
+        let keeper = keeper_bitset as u16;
+        compress(output_end as *mut i32, keeper, *ids);
+        output_end = output_end.add(keeper.count_ones() as usize);
+        *ids = op_add(*ids, SHIFT);
+        keeper_bitset >>= NUM_LANES;
+    }
+    output_end
+}
+
+// -------------------------------------------------------------------------------------------
+// Bitset output
+
+/// AVX-512 version of [`crate::filter_to_bitset`].
+pub fn filter_to_bitset<T: Avx512Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    bitset: &mut [u64],
+) {
+    assert!(is_available(), "AVX-512F and AVX-512BW are not available on this CPU");
+    crate::assert_bitset_fits(input.len(), bitset.len());
+    let Some(range) = crate::to_inclusive(range) else {
+        bitset[..input.len().div_ceil(64)].fill(0);
+        return;
+    };
+    unsafe {
+        let predicate = InRange::new(range);
+        filter_to_bitset_aux(input.as_ptr(), input.len(), &predicate, bitset.as_mut_ptr());
+    }
+}
+
+#[target_feature(enable = "avx512f,avx512bw")]
+unsafe fn filter_to_bitset_aux<T: Avx512Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+    bitset: *mut u64,
+) {
+    // `T::NUM_LANES` divides 64, so each u64 of the bitset is made of whole words.
+    let words_per_u64 = 64 / T::NUM_LANES;
+    let num_words = input_len / T::NUM_LANES;
+    let mut bits = 0u64;
+    for word_id in 0..num_words {
+        let word = input.add(word_id * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset(word);
+        let shift = (word_id % words_per_u64) * T::NUM_LANES;
+        bits |= keeper_bitset << shift;
+        if word_id % words_per_u64 == words_per_u64 - 1 {
+            *bitset.add(word_id / words_per_u64) = bits;
+            bits = 0;
+        }
+    }
+    let tail_len = input_len % T::NUM_LANES;
+    if tail_len > 0 {
+        let tail_mask = (1u64 << tail_len) - 1;
+        let word = input.add(num_words * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset_masked(word, tail_mask);
+        bits |= keeper_bitset << ((num_words % words_per_u64) * T::NUM_LANES);
+    }
+    if !input_len.is_multiple_of(64) {
+        *bitset.add(input_len / 64) = bits;
+    }
+}
+
+// -------------------------------------------------------------------------------------------
+// Count
+
+/// AVX-512 version of [`crate::count_in_range`].
+///
+/// Also requires POPCNT, which every AVX-512 CPU has.
+pub fn count_in_range<T: Avx512Element>(input: &[T], range: impl RangeBounds<T>) -> usize {
+    assert!(is_available(), "AVX-512F and AVX-512BW are not available on this CPU");
+    assert!(is_x86_feature_detected!("popcnt"), "POPCNT is not available on this CPU");
+    let Some(range) = crate::to_inclusive(range) else {
+        return 0;
+    };
+    unsafe { count_aux(input.as_ptr(), input.len(), &InRange::new(range)) }
+}
+
+#[target_feature(enable = "avx512f,avx512bw,popcnt")]
+unsafe fn count_aux<T: Avx512Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+) -> usize {
+    let num_words = input_len / T::NUM_LANES;
+    let mut count = 0;
+    for word_id in 0..num_words {
+        let word = input.add(word_id * T::NUM_LANES);
+        count += predicate.compute_filter_bitset(word).count_ones() as usize;
+    }
+    let tail_len = input_len % T::NUM_LANES;
+    if tail_len > 0 {
+        let tail_mask = (1u64 << tail_len) - 1;
+        let word = input.add(num_words * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset_masked(word, tail_mask);
+        count += keeper_bitset.count_ones() as usize;
+    }
+    count
+}
+
+// -------------------------------------------------------------------------------------------
+// Equality
+
+/// AVX-512 version of [`crate::filter_eq`].
+pub fn filter_eq<T: Avx512Element>(input: &[T], value: T, output: &mut Vec<u32>) {
+    assert!(is_available(), "AVX-512F and AVX-512BW are not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    output.clear();
+    unsafe {
+        append_ids(input, &Equal::new(value), 0, output);
+    }
+}
+
+/// AVX-512 version of [`crate::filter_ne`].
+pub fn filter_ne<T: Avx512Element>(input: &[T], value: T, output: &mut Vec<u32>) {
+    assert!(is_available(), "AVX-512F and AVX-512BW are not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    output.clear();
+    unsafe {
+        append_ids(input, &NotEqual(Equal::new(value)), 0, output);
+    }
+}
+
+// -------------------------------------------------------------------------------------------
+// Multiple ranges
+
+/// AVX-512 version of [`crate::filter_vec_multi_range`].
+pub fn filter_vec_multi_range<T: Avx512Element>(
+    input: &[T],
+    ranges: &[RangeInclusive<T>],
+    output: &mut Vec<u32>,
+) {
+    assert!(is_available(), "AVX-512F and AVX-512BW are not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    output.clear();
+    let ranges: Vec<RangeInclusive<T>> = ranges
+        .iter()
+        .filter(|range| !range.is_empty())
+        .cloned()
+        .collect();
+    if ranges.is_empty() {
+        return;
+    }
+    unsafe {
+        append_ids(input, &AnyInRange::new(ranges), 0, output);
+    }
+}
+
+// -------------------------------------------------------------------------------------------
+// Negated range
+
+/// AVX-512 version of [`crate::filter_vec_outside`].
+pub fn filter_vec_outside<T: Avx512Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    output: &mut Vec<u32>,
+) {
+    assert!(is_available(), "AVX-512F and AVX-512BW are not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    output.clear();
+    let Some(range) = crate::to_inclusive(range) else {
+        output.extend(0..input.len() as u32);
+        return;
+    };
+    unsafe {
+        append_ids(input, &Outside(InRange::new(range)), 0, output);
+    }
+}
+
+// -------------------------------------------------------------------------------------------
+// Set membership
+
+/// AVX-512 version of [`crate::filter_in_set`].
+pub fn filter_in_set(input: &[u32], values: &[u32], output: &mut Vec<u32>) {
+    assert!(is_available(), "AVX-512F and AVX-512BW are not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    output.clear();
+    let set = U32Set::new(values);
+    unsafe {
+        match &set {
+            U32Set::Empty => {}
+            U32Set::Broadcast(values) => append_ids(input, &AnyOf::new(values), 0, output),
+            U32Set::Sorted(values) => append_ids(input, &SortedSearch { values }, 0, output),
+            U32Set::Bitmap { min, span, words } => {
+                append_ids(input, &BitmapLookup::new(*min, *span, words), 0, output);
+            }
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------
+// Selection
+
+/// AVX-512 version of [`crate::filter_selection`].
+pub fn filter_selection(
+    column: &[u32],
+    candidate_ids: &[u32],
+    range: impl RangeBounds<u32>,
+    output: &mut Vec<u32>,
+) {
+    assert!(is_available(), "AVX-512F and AVX-512BW are not available on this CPU");
+    crate::assert_ids_in_bounds(candidate_ids, column.len());
+    output.clear();
+    let Some(range) = crate::to_inclusive(range) else {
+        return;
+    };
+    if column.len() > i32::MAX as usize {
+        // Gather indices are signed 32-bit integers.
+        return crate::scalar::filter_selection(column, candidate_ids, range, output);
+    }
+    unsafe {
+        let range_simd = u32::simd_range(&range);
+        for block in candidate_ids.chunks(crate::OUTPUT_BLOCK_LEN) {
+            output.reserve(block.len());
+            let initial_len = output.len();
+            let output_len = filter_selection_aux(
+                column.as_ptr(),
+                block,
+                &range_simd,
+                output.as_mut_ptr().add(initial_len),
+            );
+            output.set_len(initial_len + output_len);
+        }
+    }
+}
+
+/// `output` must have room for `candidate_ids.len()` ids, which must all be
+/// valid indices of `column`, and lower than `i32::MAX`.
+#[target_feature(enable = "avx512f,avx512bw")]
+unsafe fn filter_selection_aux(
+    column: *const u32,
+    candidate_ids: &[u32],
+    range: &OffsetRange,
+    output: *mut u32,
+) -> usize {
+    let column = column as *const i32;
+    let mut output_end = output;
+    let mut chunks = candidate_ids.chunks_exact(NUM_LANES);
+    for chunk in &mut chunks {
+        let ids = _mm512_loadu_epi32(chunk.as_ptr() as *const i32);
+        let vals = _mm512_i32gather_epi32::<4>(ids, column);
+        let keeper = _mm512_cmple_epu32_mask(_mm512_sub_epi32(vals, range.start), range.width);
+        compress(output_end as *mut i32, keeper, ids);
+        output_end = output_end.add(keeper.count_ones() as usize);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        // Masked-out lanes are neither read, gathered nor emitted.
+        let tail_mask = ((1u32 << tail.len()) - 1) as u16;
+        let ids = _mm512_maskz_loadu_epi32(tail_mask, tail.as_ptr() as *const i32);
+        let vals = _mm512_mask_i32gather_epi32::<4>(_mm512_setzero_si512(), tail_mask, ids, column);
+        let offset = _mm512_sub_epi32(vals, range.start);
+        let keeper = _mm512_mask_cmple_epu32_mask(tail_mask, offset, range.width);
+        compress(output_end as *mut i32, keeper, ids);
+        output_end = output_end.add(keeper.count_ones() as usize);
+    }
+    output_end.offset_from(output) as usize
+}
+
+// -------------------------------------------------------------------------------------------
+// Values output
+
+/// AVX-512 version of [`crate::filter_values`].
+pub fn filter_values<T: Avx512Element>(input: &[T], range: impl RangeBounds<T>, output: &mut Vec<T>) {
+    assert!(is_available(), "AVX-512F and AVX-512BW are not available on this CPU");
+    output.clear();
+    let Some(range) = crate::to_inclusive(range) else {
+        return;
+    };
+    unsafe {
+        let predicate = InRange::new(range);
+        for block in input.chunks(crate::OUTPUT_BLOCK_LEN) {
+            output.reserve(block.len());
+            let initial_len = output.len();
+            let values = output.as_mut_ptr().add(initial_len);
+            let output_len = filter_values_aux(block.as_ptr(), block.len(), &predicate, values);
+            output.set_len(initial_len + output_len);
+        }
+    }
+}
+
+/// AVX-512 version of [`crate::filter_ids_and_values`].
+pub fn filter_ids_and_values<T: Avx512Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    ids: &mut Vec<u32>,
+    values: &mut Vec<T>,
+) {
+    assert!(is_available(), "AVX-512F and AVX-512BW are not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    ids.clear();
+    values.clear();
+    let Some(range) = crate::to_inclusive(range) else {
+        return;
+    };
+    unsafe {
+        let predicate = InRange::new(range);
+        for (block_id, block) in input.chunks(crate::OUTPUT_BLOCK_LEN).enumerate() {
+            ids.reserve(block.len());
+            values.reserve(block.len());
+            let initial_len = ids.len();
+            let output_len = filter_ids_and_values_aux(
+                block.as_ptr(),
+                block.len(),
+                &predicate,
+                (block_id * crate::OUTPUT_BLOCK_LEN) as u32,
+                ids.as_mut_ptr().add(initial_len),
+                values.as_mut_ptr().add(initial_len),
+            );
+            ids.set_len(initial_len + output_len);
+            values.set_len(initial_len + output_len);
+        }
+    }
+}
+
+/// `values` must have room for `input_len` elements.
+///
+/// `values` may also be `input` itself: the slots written never go past the
+/// elements already read.
+#[target_feature(enable = "avx512f,avx512bw")]
+unsafe fn filter_values_aux<T: Avx512Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+    values: *mut T,
+) -> usize {
+    let num_words = input_len / T::NUM_LANES;
+    let mut values_end = values;
+    for word_id in 0..num_words {
+        let word = input.add(word_id * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset(word);
+        values_end = T::write_kept(values_end, word, keeper_bitset);
+    }
+    let tail_len = input_len % T::NUM_LANES;
+    if tail_len > 0 {
+        let tail_mask = (1u64 << tail_len) - 1;
+        let word = input.add(num_words * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset_masked(word, tail_mask);
+        values_end = T::write_kept(values_end, word, keeper_bitset);
+    }
+    values_end.offset_from(values) as usize
+}
+
+/// `ids` and `values` must both have room for `input_len` elements.
+#[target_feature(enable = "avx512f,avx512bw")]
+unsafe fn filter_ids_and_values_aux<T: Avx512Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+    first_id: u32,
+    ids: *mut u32,
+    values: *mut T,
+) -> usize {
+    let num_words = input_len / T::NUM_LANES;
+    let mut ids_end = ids;
+    let mut values_end = values;
+    let mut ids_simd = op_add(
+        from_u32x16([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]),
+        _mm512_set1_epi32(first_id as i32),
+    );
+    for word_id in 0..num_words {
+        let word = input.add(word_id * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset(word);
+        ids_end = write_ids(ids_end, &mut ids_simd, keeper_bitset, T::NUM_LANES);
+        values_end = T::write_kept(values_end, word, keeper_bitset);
+    }
+    let tail_len = input_len % T::NUM_LANES;
+    if tail_len > 0 {
+        let tail_mask = (1u64 << tail_len) - 1;
+        let word = input.add(num_words * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset_masked(word, tail_mask);
+        ids_end = write_ids(ids_end, &mut ids_simd, keeper_bitset, T::NUM_LANES);
+        T::write_kept(values_end, word, keeper_bitset);
+    }
+    ids_end.offset_from(ids) as usize
+}
+
+// -------------------------------------------------------------------------------------------
+// In-place retain
+
+/// AVX-512 version of [`crate::retain_in_range`].
+pub fn retain_in_range<T: Avx512Element>(values: &mut Vec<T>, range: impl RangeBounds<T>) {
+    assert!(is_available(), "AVX-512F and AVX-512BW are not available on this CPU");
+    let Some(range) = crate::to_inclusive(range) else {
+        values.clear();
+        return;
+    };
+    unsafe {
+        let predicate = InRange::new(range);
+        let values_ptr = values.as_mut_ptr();
+        let new_len = filter_values_aux(values_ptr, values.len(), &predicate, values_ptr);
+        values.set_len(new_len);
+    }
+}
+
+// -------------------------------------------------------------------------------------------
+// Partition
+
+/// AVX-512 version of [`crate::partition_by_range`].
+pub fn partition_by_range<T: Avx512Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    inside: &mut Vec<u32>,
+    outside: &mut Vec<u32>,
+) {
+    assert!(is_available(), "AVX-512F and AVX-512BW are not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    inside.clear();
+    outside.clear();
+    let Some(range) = crate::to_inclusive(range) else {
+        outside.extend(0..input.len() as u32);
+        return;
+    };
+    unsafe {
+        let predicate = InRange::new(range);
+        for (block_id, block) in input.chunks(crate::OUTPUT_BLOCK_LEN).enumerate() {
+            inside.reserve(block.len());
+            outside.reserve(block.len());
+            let (inside_initial_len, outside_initial_len) = (inside.len(), outside.len());
+            let (inside_len, outside_len) = partition_aux(
+                block.as_ptr(),
+                block.len(),
+                &predicate,
+                (block_id * crate::OUTPUT_BLOCK_LEN) as u32,
+                inside.as_mut_ptr().add(inside_initial_len),
+                outside.as_mut_ptr().add(outside_initial_len),
+            );
+            inside.set_len(inside_initial_len + inside_len);
+            outside.set_len(outside_initial_len + outside_len);
+        }
+    }
+}
+
+/// `inside` and `outside` must both have room for `input_len` ids.
+#[target_feature(enable = "avx512f,avx512bw")]
+unsafe fn partition_aux<T: Avx512Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+    first_id: u32,
+    inside: *mut u32,
+    outside: *mut u32,
+) -> (usize, usize) {
+    let num_words = input_len / T::NUM_LANES;
+    let mut inside_end = inside;
+    let mut outside_end = outside;
+    let mut ids = op_add(
+        from_u32x16([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]),
+        _mm512_set1_epi32(first_id as i32),
+    );
+    for word_id in 0..num_words {
+        let word = input.add(word_id * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset(word);
+        let mut outside_ids = ids;
+        let outside_bitset = !keeper_bitset & word_mask::<T>();
+        outside_end = write_ids(outside_end, &mut outside_ids, outside_bitset, T::NUM_LANES);
+        inside_end = write_ids(inside_end, &mut ids, keeper_bitset, T::NUM_LANES);
+    }
+    let tail_len = input_len % T::NUM_LANES;
+    if tail_len > 0 {
+        let tail_mask = (1u64 << tail_len) - 1;
+        let word = input.add(num_words * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset_masked(word, tail_mask);
+        let mut outside_ids = ids;
+        outside_end = write_ids(outside_end, &mut outside_ids, !keeper_bitset & tail_mask, T::NUM_LANES);
+        inside_end = write_ids(inside_end, &mut ids, keeper_bitset, T::NUM_LANES);
+    }
+    (
+        inside_end.offset_from(inside) as usize,
+        outside_end.offset_from(outside) as usize,
+    )
+}
+
+// -------------------------------------------------------------------------------------------
+// Early exit
+
+/// AVX-512 version of [`crate::any_in_range`].
+pub fn any_in_range<T: Avx512Element>(input: &[T], range: impl RangeBounds<T>) -> bool {
+    position_first_in_range(input, range).is_some()
+}
+
+/// AVX-512 version of [`crate::position_first_in_range`].
+pub fn position_first_in_range<T: Avx512Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+) -> Option<usize> {
+    assert!(is_available(), "AVX-512F and AVX-512BW are not available on this CPU");
+    let range = crate::to_inclusive(range)?;
+    unsafe { position_first_aux(input.as_ptr(), input.len(), &InRange::new(range)) }
+}
+
+/// AVX-512 version of [`crate::position_last_in_range`].
+pub fn position_last_in_range<T: Avx512Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+) -> Option<usize> {
+    assert!(is_available(), "AVX-512F and AVX-512BW are not available on this CPU");
+    let range = crate::to_inclusive(range)?;
+    unsafe { position_last_aux(input.as_ptr(), input.len(), &InRange::new(range)) }
+}
+
+#[target_feature(enable = "avx512f,avx512bw")]
+unsafe fn position_first_aux<T: Avx512Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+) -> Option<usize> {
+    let num_words = input_len / T::NUM_LANES;
+    for word_id in 0..num_words {
+        let word = input.add(word_id * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset(word);
+        if keeper_bitset != 0 {
+            return Some(word_id * T::NUM_LANES + keeper_bitset.trailing_zeros() as usize);
+        }
+    }
+    let tail_len = input_len % T::NUM_LANES;
+    if tail_len > 0 {
+        let tail_mask = (1u64 << tail_len) - 1;
+        let word = input.add(num_words * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset_masked(word, tail_mask);
+        if keeper_bitset != 0 {
+            return Some(num_words * T::NUM_LANES + keeper_bitset.trailing_zeros() as usize);
+        }
+    }
+    None
+}
+
+#[target_feature(enable = "avx512f,avx512bw")]
+unsafe fn position_last_aux<T: Avx512Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+) -> Option<usize> {
+    let num_words = input_len / T::NUM_LANES;
+    let tail_len = input_len % T::NUM_LANES;
+    if tail_len > 0 {
+        let tail_mask = (1u64 << tail_len) - 1;
+        let word = input.add(num_words * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset_masked(word, tail_mask);
+        if keeper_bitset != 0 {
+            return Some(num_words * T::NUM_LANES + (63 - keeper_bitset.leading_zeros()) as usize);
+        }
+    }
+    for word_id in (0..num_words).rev() {
+        let word = input.add(word_id * T::NUM_LANES);
+        let keeper_bitset = predicate.compute_filter_bitset(word);
+        if keeper_bitset != 0 {
+            return Some(word_id * T::NUM_LANES + (63 - keeper_bitset.leading_zeros()) as usize);
+        }
+    }
+    None
+}
+
+// -------------------------------------------------------------------------------------------
+// Limit
+
+/// AVX-512 version of [`crate::filter_vec_limit`].
+pub fn filter_vec_limit<T: Avx512Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    skip: usize,
+    limit: usize,
+    output: &mut Vec<u32>,
+) {
+    assert!(is_available(), "AVX-512F and AVX-512BW are not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
     output.clear();
-    output.reserve(input.len());
-    let num_words = input.len() / NUM_LANES;
+    let Some(range) = crate::to_inclusive(range) else {
+        return;
+    };
+    let limit = limit.min(input.len());
+    output.reserve(limit);
     unsafe {
-        let output_len = filter_vec_aux(
+        let predicate = InRange::new(range);
+        let output_len = filter_limit_aux(
             input.as_ptr(),
-            range,
+            input.len(),
+            &predicate,
+            skip,
+            limit,
             output.as_mut_ptr(),
-            num_words,
         );
         output.set_len(output_len);
     }
 }
 
-
-pub unsafe fn filter_vec_aux(
-    mut input: *const u32,
-    range: RangeInclusive<u32>,
+/// `output` must have room for `limit` ids.
+#[target_feature(enable = "avx512f,avx512bw")]
+unsafe fn filter_limit_aux<T: Avx512Element, P: Predicate<T>>(
+    input: *const T,
+    input_len: usize,
+    predicate: &P,
+    mut skip: usize,
+    limit: usize,
     output: *mut u32,
-    num_words: usize,
 ) -> usize {
-    let mut output_end = output;
-    let range_simd = set1(*range.start() as i32)..=set1(*range.end() as i32);
+    let num_words = input_len.div_ceil(T::NUM_LANES);
+    let mut num_written = 0;
     let mut ids = from_u32x16([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
-    const SHIFT: __m512i = from_u32x16([NUM_LANES as u32; NUM_LANES]);
-    for _ in 0..num_words {
-        let word = load_unaligned(input as *const i32);
-        let keeper_bitset = compute_filter_bitset(word, range_simd.clone());
-        compress(output_end as *mut u8, keeper_bitset, ids);
-        let added_len = keeper_bitset.count_ones();
-        output_end = output_end.offset(added_len as isize);
-        ids = op_add(ids, SHIFT);
-        input = input.offset(1);
+    for word_id in 0..num_words {
+        if num_written == limit {
+            break;
+        }
+        let word = input.add(word_id * T::NUM_LANES);
+        let word_len = T::NUM_LANES.min(input_len - word_id * T::NUM_LANES);
+        let mut keeper_bitset = if word_len == T::NUM_LANES {
+            predicate.compute_filter_bitset(word)
+        } else {
+            predicate.compute_filter_bitset_masked(word, (1u64 << word_len) - 1)
+        };
+        if skip > 0 {
+            let num_skipped = skip.min(keeper_bitset.count_ones() as usize);
+            keeper_bitset ^= crate::lowest_set_bits(keeper_bitset, num_skipped);
+            skip -= num_skipped;
+        }
+        // Only the ids kept are written: trimming the bitset is enough to stay
+        // within `limit`.
+        keeper_bitset = crate::lowest_set_bits(keeper_bitset, limit - num_written);
+        let output_end = output.add(num_written);
+        let new_end = write_ids(output_end, &mut ids, keeper_bitset, T::NUM_LANES);
+        num_written += new_end.offset_from(output_end) as usize;
+    }
+    num_written
+}
+
+// -------------------------------------------------------------------------------------------
+// Slice output
+
+/// AVX-512 version of [`crate::filter_vec_into_slice`].
+pub fn filter_vec_into_slice<T: Avx512Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    output: &mut [MaybeUninit<u32>],
+) -> usize {
+    assert!(is_available(), "AVX-512F and AVX-512BW are not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    crate::assert_output_fits(input.len(), output.len());
+    let Some(range) = crate::to_inclusive(range) else {
+        return 0;
+    };
+    unsafe {
+        let predicate = InRange::new(range);
+        filter_ids_aux(input.as_ptr(), input.len(), &predicate, 0, output.as_mut_ptr() as *mut u32)
+    }
+}
+
+// -------------------------------------------------------------------------------------------
+// Visitor
+
+/// AVX-512 version of [`crate::filter_vec_visit`].
+pub fn filter_vec_visit<T: Avx512Element>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    mut visit: impl FnMut(&[u32]),
+) {
+    assert!(is_available(), "AVX-512F and AVX-512BW are not available on this CPU");
+    crate::assert_ids_fit(0, input.len());
+    let Some(range) = crate::to_inclusive(range) else {
+        return;
+    };
+    let mut ids = [0u32; crate::VISIT_BLOCK_LEN];
+    unsafe {
+        let predicate = InRange::new(range);
+        for (block_id, block) in input.chunks(crate::VISIT_BLOCK_LEN).enumerate() {
+            let num_ids = filter_ids_aux(
+                block.as_ptr(),
+                block.len(),
+                &predicate,
+                (block_id * crate::VISIT_BLOCK_LEN) as u32,
+                ids.as_mut_ptr(),
+            );
+            if num_ids > 0 {
+                visit(&ids[..num_ids]);
+            }
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------
+// Predicates
+
+/// Test run by the kernels on every element.
+trait Predicate<T: Avx512Element> {
+    /// Loads the word starting at `word`, and returns the bitset of its
+    /// elements that pass the test.
+    unsafe fn compute_filter_bitset(&self, word: *const T) -> u64;
+
+    /// Same as `compute_filter_bitset`, but only loads the lanes set in
+    /// `load_mask`. The other lanes are never part of the result.
+    unsafe fn compute_filter_bitset_masked(&self, word: *const T, load_mask: u64) -> u64;
+}
+
+struct InRange<T: Avx512Element> {
+    range_simd: T::SimdRange,
+}
+
+impl<T: Avx512Element> InRange<T> {
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn new(range: RangeInclusive<T>) -> Self {
+        InRange { range_simd: T::simd_range(&range) }
+    }
+}
+
+impl<T: Avx512Element> Predicate<T> for InRange<T> {
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset(&self, word: *const T) -> u64 {
+        T::compute_filter_bitset(word, &self.range_simd)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset_masked(&self, word: *const T, load_mask: u64) -> u64 {
+        T::compute_filter_bitset_masked(word, load_mask, &self.range_simd)
+    }
+}
+
+/// Union of several ranges: the bitsets of the ranges are OR-ed together.
+struct AnyInRange<T: Avx512Element> {
+    ranges: Vec<InRange<T>>,
+}
+
+impl<T: Avx512Element> AnyInRange<T> {
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn new(ranges: Vec<RangeInclusive<T>>) -> Self {
+        let ranges = ranges.into_iter().map(|range| InRange::new(range)).collect();
+        AnyInRange { ranges }
+    }
+}
+
+impl<T: Avx512Element> Predicate<T> for AnyInRange<T> {
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset(&self, word: *const T) -> u64 {
+        let mut bitset = 0;
+        for range in &self.ranges {
+            bitset |= range.compute_filter_bitset(word);
+        }
+        bitset
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset_masked(&self, word: *const T, load_mask: u64) -> u64 {
+        let mut bitset = 0;
+        for range in &self.ranges {
+            bitset |= range.compute_filter_bitset_masked(word, load_mask);
+        }
+        bitset
+    }
+}
+
+/// Complement of [`InRange`]. NaN is outside of every range.
+struct Outside<T: Avx512Element>(InRange<T>);
+
+impl<T: Avx512Element> Predicate<T> for Outside<T> {
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset(&self, word: *const T) -> u64 {
+        !self.0.compute_filter_bitset(word) & word_mask::<T>()
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset_masked(&self, word: *const T, load_mask: u64) -> u64 {
+        !self.0.compute_filter_bitset_masked(word, load_mask) & load_mask
+    }
+}
+
+struct Equal<T: Avx512Element> {
+    value_simd: T::SimdValue,
+}
+
+impl<T: Avx512Element> Equal<T> {
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn new(value: T) -> Self {
+        Equal { value_simd: T::splat(value) }
+    }
+}
+
+impl<T: Avx512Element> Predicate<T> for Equal<T> {
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset(&self, word: *const T) -> u64 {
+        T::compute_eq_bitset(word, &self.value_simd)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset_masked(&self, word: *const T, load_mask: u64) -> u64 {
+        T::compute_eq_bitset_masked(word, load_mask, &self.value_simd)
+    }
+}
+
+/// Complement of [`Equal`]. Like `!=`, it accepts NaN.
+struct NotEqual<T: Avx512Element>(Equal<T>);
+
+impl<T: Avx512Element> Predicate<T> for NotEqual<T> {
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset(&self, word: *const T) -> u64 {
+        !self.0.compute_filter_bitset(word) & word_mask::<T>()
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset_masked(&self, word: *const T, load_mask: u64) -> u64 {
+        !self.0.compute_filter_bitset_masked(word, load_mask) & load_mask
+    }
+}
+
+/// Compares every element against each value of a tiny set.
+struct AnyOf {
+    values_simd: Vec<DataType>,
+}
+
+impl AnyOf {
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn new(values: &[u32]) -> Self {
+        let values_simd = values
+            .iter()
+            .map(|&value| _mm512_set1_epi32(value as i32))
+            .collect();
+        AnyOf { values_simd }
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn matches(&self, val: DataType) -> u64 {
+        let mut matches = 0;
+        for &value in &self.values_simd {
+            matches |= _mm512_cmpeq_epi32_mask(val, value);
+        }
+        matches as u64
+    }
+}
+
+impl Predicate<u32> for AnyOf {
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset(&self, word: *const u32) -> u64 {
+        self.matches(_mm512_loadu_si512(word as *const DataType))
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset_masked(&self, word: *const u32, load_mask: u64) -> u64 {
+        self.matches(_mm512_maskz_loadu_epi32(load_mask as u16, word as *const i32)) & load_mask
+    }
+}
+
+/// Branchless binary search of every lane in a sorted set, with one gather per step.
+struct SortedSearch<'a> {
+    values: &'a [u32],
+}
+
+impl SortedSearch<'_> {
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn matches(&self, val: DataType) -> u64 {
+        let values = self.values.as_ptr() as *const i32;
+        // `base` ends up on the last value lower or equal to `val`, if any.
+        let mut base = _mm512_setzero_si512();
+        let mut len = self.values.len();
+        while len > 1 {
+            let half = len / 2;
+            let probe = _mm512_i32gather_epi32::<4>(op_add(base, _mm512_set1_epi32(half as i32)), values);
+            let not_too_high = _mm512_cmple_epu32_mask(probe, val);
+            base = _mm512_mask_add_epi32(base, not_too_high, base, _mm512_set1_epi32(half as i32));
+            len -= half;
+        }
+        let found = _mm512_i32gather_epi32::<4>(base, values);
+        _mm512_cmpeq_epi32_mask(found, val) as u64
+    }
+}
+
+impl Predicate<u32> for SortedSearch<'_> {
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset(&self, word: *const u32) -> u64 {
+        self.matches(_mm512_loadu_si512(word as *const DataType))
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset_masked(&self, word: *const u32, load_mask: u64) -> u64 {
+        self.matches(_mm512_maskz_loadu_epi32(load_mask as u16, word as *const i32)) & load_mask
+    }
+}
+
+/// Looks every lane up in the bitmap of a dense set, with a masked gather.
+struct BitmapLookup<'a> {
+    words: &'a [u32],
+    min: DataType,
+    span: DataType,
+}
+
+impl<'a> BitmapLookup<'a> {
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn new(min: u32, span: u32, words: &'a [u32]) -> Self {
+        BitmapLookup {
+            words,
+            min: _mm512_set1_epi32(min as i32),
+            span: _mm512_set1_epi32(span as i32),
+        }
+    }
+
+    /// Only the lanes of `lane_mask` are looked up.
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn matches(&self, val: DataType, lane_mask: u16) -> u64 {
+        let offset = _mm512_sub_epi32(val, self.min);
+        let in_domain = _mm512_mask_cmplt_epu32_mask(lane_mask, offset, self.span);
+        let bitmap_word = _mm512_mask_i32gather_epi32::<4>(
+            _mm512_setzero_si512(),
+            in_domain,
+            _mm512_srli_epi32::<5>(offset),
+            self.words.as_ptr() as *const i32,
+        );
+        let bit = _mm512_srlv_epi32(bitmap_word, _mm512_and_si512(offset, _mm512_set1_epi32(31)));
+        _mm512_test_epi32_mask(bit, _mm512_set1_epi32(1)) as u64
+    }
+}
+
+impl Predicate<u32> for BitmapLookup<'_> {
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset(&self, word: *const u32) -> u64 {
+        self.matches(_mm512_loadu_si512(word as *const DataType), u16::MAX)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset_masked(&self, word: *const u32, load_mask: u64) -> u64 {
+        let load_mask = load_mask as u16;
+        self.matches(_mm512_maskz_loadu_epi32(load_mask, word as *const i32), load_mask)
+    }
+}
+
+/// Bitset with one bit set per element of a word.
+#[inline]
+fn word_mask<T: Avx512Element>() -> u64 {
+    u64::MAX >> (64 - T::NUM_LANES)
+}
+
+// -------------------------------------------------------------------------------------------
+// Element types
+
+/// Integer range `start..=end`, tested as `x - start <= end - start` in unsigned
+/// arithmetic: a subtraction and a single comparison, whatever the signedness.
+#[doc(hidden)]
+#[derive(Clone, Copy)]
+pub struct OffsetRange {
+    start: DataType,
+    width: DataType,
+}
+
+impl Avx512Element for u32 {
+    const NUM_LANES: usize = 16;
+
+    type SimdRange = OffsetRange;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn simd_range(range: &RangeInclusive<u32>) -> Self::SimdRange {
+        // `x - start <= end - start` holds, in unsigned arithmetic, iff `x`
+        // is in range: a single comparison, even for signed elements.
+        OffsetRange {
+            start: _mm512_set1_epi32(*range.start() as i32),
+            width: _mm512_set1_epi32(range.end().wrapping_sub(*range.start()) as i32),
+        }
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset(word: *const u32, range: &Self::SimdRange) -> u64 {
+        let val = _mm512_loadu_epi32(word as *const i32);
+        _mm512_cmple_epu32_mask(_mm512_sub_epi32(val, range.start), range.width) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset_masked(
+        word: *const u32,
+        load_mask: u64,
+        range: &Self::SimdRange,
+    ) -> u64 {
+        let load_mask = load_mask as u16;
+        let val = _mm512_maskz_loadu_epi32(load_mask, word as *const i32);
+        let offset = _mm512_sub_epi32(val, range.start);
+        _mm512_mask_cmple_epu32_mask(load_mask, offset, range.width) as u64
+    }
+
+    type SimdValue = DataType;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn splat(value: u32) -> Self::SimdValue {
+        _mm512_set1_epi32(value as i32)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset(word: *const u32, value: &Self::SimdValue) -> u64 {
+        let val = _mm512_loadu_epi32(word as *const i32);
+        _mm512_cmpeq_epi32_mask(val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset_masked(
+        word: *const u32,
+        load_mask: u64,
+        value: &Self::SimdValue,
+    ) -> u64 {
+        let load_mask = load_mask as u16;
+        let val = _mm512_maskz_loadu_epi32(load_mask, word as *const i32);
+        _mm512_mask_cmpeq_epi32_mask(load_mask, val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn write_kept(output_end: *mut u32, word: *const u32, keeper_bitset: u64) -> *mut u32 {
+        write_kept_epi32(output_end, word, keeper_bitset)
+    }
+}
+
+impl Avx512Element for i32 {
+    const NUM_LANES: usize = 16;
+
+    type SimdRange = OffsetRange;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn simd_range(range: &RangeInclusive<i32>) -> Self::SimdRange {
+        OffsetRange {
+            start: _mm512_set1_epi32(*range.start()),
+            width: _mm512_set1_epi32(range.end().wrapping_sub(*range.start())),
+        }
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset(word: *const i32, range: &Self::SimdRange) -> u64 {
+        let val = _mm512_loadu_epi32(word);
+        _mm512_cmple_epu32_mask(_mm512_sub_epi32(val, range.start), range.width) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset_masked(
+        word: *const i32,
+        load_mask: u64,
+        range: &Self::SimdRange,
+    ) -> u64 {
+        let load_mask = load_mask as u16;
+        let val = _mm512_maskz_loadu_epi32(load_mask, word);
+        let offset = _mm512_sub_epi32(val, range.start);
+        _mm512_mask_cmple_epu32_mask(load_mask, offset, range.width) as u64
+    }
+
+    type SimdValue = DataType;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn splat(value: i32) -> Self::SimdValue {
+        _mm512_set1_epi32(value)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset(word: *const i32, value: &Self::SimdValue) -> u64 {
+        let val = _mm512_loadu_epi32(word);
+        _mm512_cmpeq_epi32_mask(val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset_masked(
+        word: *const i32,
+        load_mask: u64,
+        value: &Self::SimdValue,
+    ) -> u64 {
+        let load_mask = load_mask as u16;
+        let val = _mm512_maskz_loadu_epi32(load_mask, word);
+        _mm512_mask_cmpeq_epi32_mask(load_mask, val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn write_kept(output_end: *mut i32, word: *const i32, keeper_bitset: u64) -> *mut i32 {
+        write_kept_epi32(output_end as *mut u32, word as *const u32, keeper_bitset) as *mut i32
+    }
+}
+
+impl Avx512Element for u64 {
+    const NUM_LANES: usize = 8;
+
+    type SimdRange = OffsetRange;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn simd_range(range: &RangeInclusive<u64>) -> Self::SimdRange {
+        OffsetRange {
+            start: _mm512_set1_epi64(*range.start() as i64),
+            width: _mm512_set1_epi64(range.end().wrapping_sub(*range.start()) as i64),
+        }
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset(word: *const u64, range: &Self::SimdRange) -> u64 {
+        let val = _mm512_loadu_epi64(word as *const i64);
+        _mm512_cmple_epu64_mask(_mm512_sub_epi64(val, range.start), range.width) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset_masked(
+        word: *const u64,
+        load_mask: u64,
+        range: &Self::SimdRange,
+    ) -> u64 {
+        let load_mask = load_mask as u8;
+        let val = _mm512_maskz_loadu_epi64(load_mask, word as *const i64);
+        let offset = _mm512_sub_epi64(val, range.start);
+        _mm512_mask_cmple_epu64_mask(load_mask, offset, range.width) as u64
+    }
+
+    type SimdValue = DataType;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn splat(value: u64) -> Self::SimdValue {
+        _mm512_set1_epi64(value as i64)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset(word: *const u64, value: &Self::SimdValue) -> u64 {
+        let val = _mm512_loadu_epi64(word as *const i64);
+        _mm512_cmpeq_epi64_mask(val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset_masked(
+        word: *const u64,
+        load_mask: u64,
+        value: &Self::SimdValue,
+    ) -> u64 {
+        let load_mask = load_mask as u8;
+        let val = _mm512_maskz_loadu_epi64(load_mask, word as *const i64);
+        _mm512_mask_cmpeq_epi64_mask(load_mask, val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn write_kept(output_end: *mut u64, word: *const u64, keeper_bitset: u64) -> *mut u64 {
+        write_kept_epi64(output_end, word, keeper_bitset)
+    }
+}
+
+impl Avx512Element for i64 {
+    const NUM_LANES: usize = 8;
+
+    type SimdRange = OffsetRange;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn simd_range(range: &RangeInclusive<i64>) -> Self::SimdRange {
+        OffsetRange {
+            start: _mm512_set1_epi64(*range.start()),
+            width: _mm512_set1_epi64(range.end().wrapping_sub(*range.start())),
+        }
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset(word: *const i64, range: &Self::SimdRange) -> u64 {
+        let val = _mm512_loadu_epi64(word);
+        _mm512_cmple_epu64_mask(_mm512_sub_epi64(val, range.start), range.width) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset_masked(
+        word: *const i64,
+        load_mask: u64,
+        range: &Self::SimdRange,
+    ) -> u64 {
+        let load_mask = load_mask as u8;
+        let val = _mm512_maskz_loadu_epi64(load_mask, word);
+        let offset = _mm512_sub_epi64(val, range.start);
+        _mm512_mask_cmple_epu64_mask(load_mask, offset, range.width) as u64
+    }
+
+    type SimdValue = DataType;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn splat(value: i64) -> Self::SimdValue {
+        _mm512_set1_epi64(value)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset(word: *const i64, value: &Self::SimdValue) -> u64 {
+        let val = _mm512_loadu_epi64(word);
+        _mm512_cmpeq_epi64_mask(val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset_masked(
+        word: *const i64,
+        load_mask: u64,
+        value: &Self::SimdValue,
+    ) -> u64 {
+        let load_mask = load_mask as u8;
+        let val = _mm512_maskz_loadu_epi64(load_mask, word);
+        _mm512_mask_cmpeq_epi64_mask(load_mask, val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn write_kept(output_end: *mut i64, word: *const i64, keeper_bitset: u64) -> *mut i64 {
+        write_kept_epi64(output_end as *mut u64, word as *const u64, keeper_bitset) as *mut i64
+    }
+}
+
+impl Avx512Element for f32 {
+    const NUM_LANES: usize = 16;
+
+    type SimdRange = RangeInclusive<__m512>;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn simd_range(range: &RangeInclusive<f32>) -> Self::SimdRange {
+        _mm512_set1_ps(*range.start())..=_mm512_set1_ps(*range.end())
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset(word: *const f32, range: &Self::SimdRange) -> u64 {
+        // Ordered comparisons are false for NaN, which is never in range.
+        let val = _mm512_loadu_ps(word);
+        let low = _mm512_cmp_ps_mask::<_CMP_LE_OQ>(*range.start(), val);
+        let high = _mm512_cmp_ps_mask::<_CMP_LE_OQ>(val, *range.end());
+        (low & high) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset_masked(
+        word: *const f32,
+        load_mask: u64,
+        range: &Self::SimdRange,
+    ) -> u64 {
+        let load_mask = load_mask as u16;
+        let val = _mm512_maskz_loadu_ps(load_mask, word);
+        let low = _mm512_mask_cmp_ps_mask::<_CMP_LE_OQ>(load_mask, *range.start(), val);
+        let high = _mm512_cmp_ps_mask::<_CMP_LE_OQ>(val, *range.end());
+        (low & high) as u64
+    }
+
+    type SimdValue = __m512;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn splat(value: f32) -> Self::SimdValue {
+        _mm512_set1_ps(value)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset(word: *const f32, value: &Self::SimdValue) -> u64 {
+        let val = _mm512_loadu_ps(word);
+        _mm512_cmp_ps_mask::<_CMP_EQ_OQ>(val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset_masked(
+        word: *const f32,
+        load_mask: u64,
+        value: &Self::SimdValue,
+    ) -> u64 {
+        let load_mask = load_mask as u16;
+        let val = _mm512_maskz_loadu_ps(load_mask, word);
+        _mm512_mask_cmp_ps_mask::<_CMP_EQ_OQ>(load_mask, val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn write_kept(output_end: *mut f32, word: *const f32, keeper_bitset: u64) -> *mut f32 {
+        write_kept_epi32(output_end as *mut u32, word as *const u32, keeper_bitset) as *mut f32
+    }
+}
+
+impl Avx512Element for f64 {
+    const NUM_LANES: usize = 8;
+
+    type SimdRange = RangeInclusive<__m512d>;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn simd_range(range: &RangeInclusive<f64>) -> Self::SimdRange {
+        _mm512_set1_pd(*range.start())..=_mm512_set1_pd(*range.end())
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset(word: *const f64, range: &Self::SimdRange) -> u64 {
+        let val = _mm512_loadu_pd(word);
+        let low = _mm512_cmp_pd_mask::<_CMP_LE_OQ>(*range.start(), val);
+        let high = _mm512_cmp_pd_mask::<_CMP_LE_OQ>(val, *range.end());
+        (low & high) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset_masked(
+        word: *const f64,
+        load_mask: u64,
+        range: &Self::SimdRange,
+    ) -> u64 {
+        let load_mask = load_mask as u8;
+        let val = _mm512_maskz_loadu_pd(load_mask, word);
+        let low = _mm512_mask_cmp_pd_mask::<_CMP_LE_OQ>(load_mask, *range.start(), val);
+        let high = _mm512_cmp_pd_mask::<_CMP_LE_OQ>(val, *range.end());
+        (low & high) as u64
+    }
+
+    type SimdValue = __m512d;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn splat(value: f64) -> Self::SimdValue {
+        _mm512_set1_pd(value)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset(word: *const f64, value: &Self::SimdValue) -> u64 {
+        let val = _mm512_loadu_pd(word);
+        _mm512_cmp_pd_mask::<_CMP_EQ_OQ>(val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset_masked(
+        word: *const f64,
+        load_mask: u64,
+        value: &Self::SimdValue,
+    ) -> u64 {
+        let load_mask = load_mask as u8;
+        let val = _mm512_maskz_loadu_pd(load_mask, word);
+        _mm512_mask_cmp_pd_mask::<_CMP_EQ_OQ>(load_mask, val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn write_kept(output_end: *mut f64, word: *const f64, keeper_bitset: u64) -> *mut f64 {
+        write_kept_epi64(output_end as *mut u64, word as *const u64, keeper_bitset) as *mut f64
+    }
+}
+
+impl Avx512Element for u8 {
+    const NUM_LANES: usize = 64;
+
+    type SimdRange = OffsetRange;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn simd_range(range: &RangeInclusive<u8>) -> Self::SimdRange {
+        OffsetRange {
+            start: _mm512_set1_epi8(*range.start() as i8),
+            width: _mm512_set1_epi8(range.end().wrapping_sub(*range.start()) as i8),
+        }
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset(word: *const u8, range: &Self::SimdRange) -> u64 {
+        let val = _mm512_loadu_epi8(word as *const i8);
+        _mm512_cmple_epu8_mask(_mm512_sub_epi8(val, range.start), range.width) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset_masked(
+        word: *const u8,
+        load_mask: u64,
+        range: &Self::SimdRange,
+    ) -> u64 {
+        let val = _mm512_maskz_loadu_epi8(load_mask, word as *const i8);
+        let offset = _mm512_sub_epi8(val, range.start);
+        _mm512_mask_cmple_epu8_mask(load_mask, offset, range.width) as u64
+    }
+
+    type SimdValue = DataType;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn splat(value: u8) -> Self::SimdValue {
+        _mm512_set1_epi8(value as i8)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset(word: *const u8, value: &Self::SimdValue) -> u64 {
+        let val = _mm512_loadu_epi8(word as *const i8);
+        _mm512_cmpeq_epi8_mask(val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset_masked(
+        word: *const u8,
+        load_mask: u64,
+        value: &Self::SimdValue,
+    ) -> u64 {
+        let val = _mm512_maskz_loadu_epi8(load_mask, word as *const i8);
+        _mm512_mask_cmpeq_epi8_mask(load_mask, val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn write_kept(output_end: *mut u8, word: *const u8, keeper_bitset: u64) -> *mut u8 {
+        write_kept_scalar(output_end, word, keeper_bitset)
+    }
+}
+
+impl Avx512Element for i8 {
+    const NUM_LANES: usize = 64;
+
+    type SimdRange = OffsetRange;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn simd_range(range: &RangeInclusive<i8>) -> Self::SimdRange {
+        OffsetRange {
+            start: _mm512_set1_epi8(*range.start()),
+            width: _mm512_set1_epi8(range.end().wrapping_sub(*range.start())),
+        }
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset(word: *const i8, range: &Self::SimdRange) -> u64 {
+        let val = _mm512_loadu_epi8(word);
+        _mm512_cmple_epu8_mask(_mm512_sub_epi8(val, range.start), range.width) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset_masked(
+        word: *const i8,
+        load_mask: u64,
+        range: &Self::SimdRange,
+    ) -> u64 {
+        let val = _mm512_maskz_loadu_epi8(load_mask, word);
+        let offset = _mm512_sub_epi8(val, range.start);
+        _mm512_mask_cmple_epu8_mask(load_mask, offset, range.width) as u64
+    }
+
+    type SimdValue = DataType;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn splat(value: i8) -> Self::SimdValue {
+        _mm512_set1_epi8(value)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset(word: *const i8, value: &Self::SimdValue) -> u64 {
+        let val = _mm512_loadu_epi8(word);
+        _mm512_cmpeq_epi8_mask(val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset_masked(
+        word: *const i8,
+        load_mask: u64,
+        value: &Self::SimdValue,
+    ) -> u64 {
+        let val = _mm512_maskz_loadu_epi8(load_mask, word);
+        _mm512_mask_cmpeq_epi8_mask(load_mask, val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn write_kept(output_end: *mut i8, word: *const i8, keeper_bitset: u64) -> *mut i8 {
+        write_kept_scalar(output_end, word, keeper_bitset)
+    }
+}
+
+impl Avx512Element for u16 {
+    const NUM_LANES: usize = 32;
+
+    type SimdRange = OffsetRange;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn simd_range(range: &RangeInclusive<u16>) -> Self::SimdRange {
+        OffsetRange {
+            start: _mm512_set1_epi16(*range.start() as i16),
+            width: _mm512_set1_epi16(range.end().wrapping_sub(*range.start()) as i16),
+        }
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset(word: *const u16, range: &Self::SimdRange) -> u64 {
+        let val = _mm512_loadu_epi16(word as *const i16);
+        _mm512_cmple_epu16_mask(_mm512_sub_epi16(val, range.start), range.width) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset_masked(
+        word: *const u16,
+        load_mask: u64,
+        range: &Self::SimdRange,
+    ) -> u64 {
+        let load_mask = load_mask as u32;
+        let val = _mm512_maskz_loadu_epi16(load_mask, word as *const i16);
+        let offset = _mm512_sub_epi16(val, range.start);
+        _mm512_mask_cmple_epu16_mask(load_mask, offset, range.width) as u64
+    }
+
+    type SimdValue = DataType;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn splat(value: u16) -> Self::SimdValue {
+        _mm512_set1_epi16(value as i16)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset(word: *const u16, value: &Self::SimdValue) -> u64 {
+        let val = _mm512_loadu_epi16(word as *const i16);
+        _mm512_cmpeq_epi16_mask(val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset_masked(
+        word: *const u16,
+        load_mask: u64,
+        value: &Self::SimdValue,
+    ) -> u64 {
+        let load_mask = load_mask as u32;
+        let val = _mm512_maskz_loadu_epi16(load_mask, word as *const i16);
+        _mm512_mask_cmpeq_epi16_mask(load_mask, val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn write_kept(output_end: *mut u16, word: *const u16, keeper_bitset: u64) -> *mut u16 {
+        write_kept_scalar(output_end, word, keeper_bitset)
+    }
+}
+
+impl Avx512Element for i16 {
+    const NUM_LANES: usize = 32;
+
+    type SimdRange = OffsetRange;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn simd_range(range: &RangeInclusive<i16>) -> Self::SimdRange {
+        OffsetRange {
+            start: _mm512_set1_epi16(*range.start()),
+            width: _mm512_set1_epi16(range.end().wrapping_sub(*range.start())),
+        }
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset(word: *const i16, range: &Self::SimdRange) -> u64 {
+        let val = _mm512_loadu_epi16(word);
+        _mm512_cmple_epu16_mask(_mm512_sub_epi16(val, range.start), range.width) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_filter_bitset_masked(
+        word: *const i16,
+        load_mask: u64,
+        range: &Self::SimdRange,
+    ) -> u64 {
+        let load_mask = load_mask as u32;
+        let val = _mm512_maskz_loadu_epi16(load_mask, word);
+        let offset = _mm512_sub_epi16(val, range.start);
+        _mm512_mask_cmple_epu16_mask(load_mask, offset, range.width) as u64
+    }
+
+    type SimdValue = DataType;
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn splat(value: i16) -> Self::SimdValue {
+        _mm512_set1_epi16(value)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset(word: *const i16, value: &Self::SimdValue) -> u64 {
+        let val = _mm512_loadu_epi16(word);
+        _mm512_cmpeq_epi16_mask(val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn compute_eq_bitset_masked(
+        word: *const i16,
+        load_mask: u64,
+        value: &Self::SimdValue,
+    ) -> u64 {
+        let load_mask = load_mask as u32;
+        let val = _mm512_maskz_loadu_epi16(load_mask, word);
+        _mm512_mask_cmpeq_epi16_mask(load_mask, val, *value) as u64
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn write_kept(output_end: *mut i16, word: *const i16, keeper_bitset: u64) -> *mut i16 {
+        write_kept_scalar(output_end, word, keeper_bitset)
     }
-    output_end.offset_from(output) as usize
 }
 
 #[inline]
-unsafe fn compute_filter_bitset(val: DataType, range: std::ops::RangeInclusive<DataType>) -> u16 {
-    let low = op_less_or_equal(*range.start(), val);
-    let high = op_less_or_equal(val, *range.end());
-    low & high
+#[target_feature(enable = "avx512f,avx512bw")]
+unsafe fn write_kept_epi32(output_end: *mut u32, word: *const u32, keeper_bitset: u64) -> *mut u32 {
+    let keeper = keeper_bitset as u16;
+    let val = _mm512_maskz_loadu_epi32(keeper, word as *const i32);
+    compress(output_end as *mut i32, keeper, val);
+    output_end.add(keeper.count_ones() as usize)
+}
+
+#[inline]
+#[target_feature(enable = "avx512f,avx512bw")]
+unsafe fn write_kept_epi64(output_end: *mut u64, word: *const u64, keeper_bitset: u64) -> *mut u64 {
+    let keeper = keeper_bitset as u8;
+    let val = _mm512_maskz_loadu_epi64(keeper, word as *const i64);
+    _mm512_mask_compressstoreu_epi64(output_end as *mut i64, keeper, val);
+    output_end.add(keeper.count_ones() as usize)
+}
+
+/// Compressing bytes and 16-bit lanes requires AVX-512 VBMI2: elements are
+/// copied one by one instead.
+#[inline]
+unsafe fn write_kept_scalar<T: Copy>(mut output_end: *mut T, word: *const T, mut keeper_bitset: u64) -> *mut T {
+    while keeper_bitset != 0 {
+        *output_end = *word.add(keeper_bitset.trailing_zeros() as usize);
+        output_end = output_end.add(1);
+        keeper_bitset &= keeper_bitset - 1;
+    }
+    output_end
 }
 
 union U8x64 {
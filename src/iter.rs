@@ -0,0 +1,109 @@
+//! Lazy iteration over the ids of the elements that belong to a range.
+
+use std::iter::FusedIterator;
+use std::ops::{RangeBounds, RangeInclusive};
+
+use crate::{avx2, avx512, scalar, Backend, FilterElement};
+
+/// Iterator over the ids of the elements of a slice that belong to a range.
+///
+/// The input is filtered lazily, a block of elements at a time, with the fastest
+/// kernel available: ids are computed as they are consumed, and stopping early
+/// skips the rest of the input. Ids can be consumed one by one, or a block at a time with
+/// [`RangeFilterIter::next_block`].
+pub struct RangeFilterIter<'a, T: FilterElement> {
+    backend: Backend,
+    input: &'a [T],
+    range: Option<RangeInclusive<T>>,
+    /// Position in `input` of the next block to filter.
+    block_start: usize,
+    /// Ids of the matches of the last filtered block.
+    ids: Vec<u32>,
+    /// Position in `ids` of the next id to yield.
+    ids_pos: usize,
+}
+
+impl<'a, T: FilterElement> RangeFilterIter<'a, T> {
+    /// Creates an iterator over the ids of the elements of `input` that belong to `range`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ids of `input` do not fit in a `u32`.
+    pub fn new(input: &'a [T], range: impl RangeBounds<T>) -> Self {
+        crate::assert_ids_fit(0, input.len());
+        RangeFilterIter {
+            backend: crate::backend(),
+            input,
+            range: crate::to_inclusive(range),
+            block_start: 0,
+            ids: Vec::with_capacity(crate::VISIT_BLOCK_LEN),
+            ids_pos: 0,
+        }
+    }
+
+    /// Returns the next ids, in order, or `None` once the input is exhausted.
+    ///
+    /// The returned block is never empty. It holds the ids not yielded yet of one block
+    /// of the input.
+    pub fn next_block(&mut self) -> Option<&[u32]> {
+        while self.ids_pos == self.ids.len() {
+            if !self.filter_next_block() {
+                return None;
+            }
+        }
+        let block = &self.ids[self.ids_pos..];
+        self.ids_pos = self.ids.len();
+        Some(block)
+    }
+
+    /// Filters the next block of the input into `ids`, and returns false if there is none.
+    fn filter_next_block(&mut self) -> bool {
+        let Some(range) = &self.range else {
+            return false;
+        };
+        if self.block_start == self.input.len() {
+            return false;
+        }
+        let block_end = self.input.len().min(self.block_start + crate::VISIT_BLOCK_LEN);
+        let block = &self.input[self.block_start..block_end];
+        let first_id = self.block_start as u32;
+        self.ids.clear();
+        // `new` checked the ids and the range, `backend()` the CPU features.
+        match self.backend {
+            Backend::Avx512 => unsafe { avx512::append_in_range(block, range.clone(), first_id, &mut self.ids) },
+            Backend::Avx2 => unsafe { avx2::append_in_range(block, range.clone(), first_id, &mut self.ids) },
+            Backend::Scalar => scalar::append_in_range(block, range.clone(), first_id, &mut self.ids),
+        }
+        self.ids_pos = 0;
+        self.block_start = block_end;
+        true
+    }
+}
+
+impl<T: FilterElement> Iterator for RangeFilterIter<'_, T> {
+    type Item = u32;
+
+    #[inline]
+    fn next(&mut self) -> Option<u32> {
+        while self.ids_pos == self.ids.len() {
+            if !self.filter_next_block() {
+                return None;
+            }
+        }
+        let id = self.ids[self.ids_pos];
+        self.ids_pos += 1;
+        Some(id)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let num_buffered = self.ids.len() - self.ids_pos;
+        let num_unfiltered = if self.range.is_some() {
+            self.input.len() - self.block_start
+        } else {
+            0
+        };
+        (num_buffered, Some(num_buffered + num_unfiltered))
+    }
+}
+
+impl<T: FilterElement> FusedIterator for RangeFilterIter<'_, T> {}
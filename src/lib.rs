@@ -1,32 +1,631 @@
-#![feature(stdsimd)]
 /*
 Benchmark results
 ====================================================
 
+Produced by `cargo bench` on a CPU with AVX-512F and AVX-512BW. Runs vary by up
+to 20%.
+
 filter-interval/scalar_iterator:
-    thrpt:  [271.47 Melem/s 272.77 Melem/s 273.97 Melem/s]
+    thrpt:  [148.04 Melem/s 149.72 Melem/s 151.48 Melem/s]
 filter-interval/scalar_forloop:
-    thrpt:  [300.44 Melem/s 300.92 Melem/s 301.38 Melem/s]
+    thrpt:  [150.05 Melem/s 153.97 Melem/s 158.15 Melem/s]
 filter-interval/scalar_nobranch:
-    thrpt:  [370.27 Melem/s 372.01 Melem/s 373.60 Melem/s]
+    thrpt:  [586.95 Melem/s 612.77 Melem/s 640.59 Melem/s]
 filter-interval/avx2
-    thrpt:  [2.2039 Gelem/s 2.2491 Gelem/s 2.2940 Gelem/s]
+    thrpt:  [2.4804 Gelem/s 2.5242 Gelem/s 2.5629 Gelem/s]
+filter-interval/avx512
+    thrpt:  [2.5169 Gelem/s 2.5757 Gelem/s 2.6303 Gelem/s]
+filter-interval/dispatch
+    thrpt:  [2.7430 Gelem/s 2.8113 Gelem/s 2.8835 Gelem/s]
+filter-interval/avx2_u32
+    thrpt:  [3.4652 Gelem/s 3.6012 Gelem/s 3.7549 Gelem/s]
+filter-interval/avx2_i64
+    thrpt:  [2.0330 Gelem/s 2.1341 Gelem/s 2.2430 Gelem/s]
+filter-interval/avx2_u8
+    thrpt:  [4.7996 Gelem/s 5.0378 Gelem/s 5.2990 Gelem/s]
+filter-interval/avx512_u32
+    thrpt:  [4.2833 Gelem/s 4.4095 Gelem/s 4.5385 Gelem/s]
+filter-interval/avx512_i64
+    thrpt:  [3.2031 Gelem/s 3.3427 Gelem/s 3.5099 Gelem/s]
+filter-interval/avx512_u8
+    thrpt:  [4.0691 Gelem/s 4.1563 Gelem/s 4.2592 Gelem/s]
+count-interval/avx2_u32
+    thrpt:  [8.2163 Gelem/s 8.4717 Gelem/s 8.7298 Gelem/s]
+count-interval/avx2_i64
+    thrpt:  [4.2163 Gelem/s 4.4419 Gelem/s 4.6694 Gelem/s]
+count-interval/avx2_u8
+    thrpt:  [30.499 Gelem/s 31.903 Gelem/s 33.306 Gelem/s]
+count-interval/avx512_u32
+    thrpt:  [11.984 Gelem/s 12.347 Gelem/s 12.737 Gelem/s]
+count-interval/avx512_i64
+    thrpt:  [6.8080 Gelem/s 7.0280 Gelem/s 7.2835 Gelem/s]
+count-interval/avx512_u8
+    thrpt:  [46.008 Gelem/s 47.130 Gelem/s 48.423 Gelem/s]
 */
 
 pub mod avx2;
 pub mod avx512;
+mod iter;
+pub mod scalar;
+mod set;
+
+pub use iter::RangeFilterIter;
+
+use std::mem::MaybeUninit;
+use std::ops::{Bound, RangeBounds, RangeInclusive};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+mod private {
+    pub trait Sealed: Copy + PartialOrd {
+        /// Lowest value of the type, infinities included.
+        const LOWEST: Self;
+        /// Highest value of the type, infinities included.
+        const HIGHEST: Self;
+
+        /// Returns the smallest value greater than `self`, if any.
+        fn successor(self) -> Option<Self>;
+
+        /// Returns the largest value smaller than `self`, if any.
+        fn predecessor(self) -> Option<Self>;
+    }
+
+    macro_rules! impl_sealed_int {
+        ($($int:ty),*) => {$(
+            impl Sealed for $int {
+                const LOWEST: Self = <$int>::MIN;
+                const HIGHEST: Self = <$int>::MAX;
+
+                fn successor(self) -> Option<Self> {
+                    self.checked_add(1)
+                }
+
+                fn predecessor(self) -> Option<Self> {
+                    self.checked_sub(1)
+                }
+            }
+        )*};
+    }
+
+    macro_rules! impl_sealed_float {
+        ($($float:ty),*) => {$(
+            impl Sealed for $float {
+                const LOWEST: Self = <$float>::NEG_INFINITY;
+                const HIGHEST: Self = <$float>::INFINITY;
+
+                fn successor(self) -> Option<Self> {
+                    (self != Self::HIGHEST).then(|| self.next_up())
+                }
+
+                fn predecessor(self) -> Option<Self> {
+                    (self != Self::LOWEST).then(|| self.next_down())
+                }
+            }
+        )*};
+    }
+
+    impl_sealed_int!(u8, i8, u16, i16, u32, i32, u64, i64);
+    impl_sealed_float!(f32, f64);
+}
+
+/// Converts `range` into the equivalent inclusive range, or `None` if it is empty.
+///
+/// For floats, unbounded ends become infinities: NaN never belongs to a range.
+pub(crate) fn to_inclusive<T: private::Sealed>(
+    range: impl RangeBounds<T>,
+) -> Option<RangeInclusive<T>> {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start.successor()?,
+        Bound::Unbounded => T::LOWEST,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end,
+        Bound::Excluded(&end) => end.predecessor()?,
+        Bound::Unbounded => T::HIGHEST,
+    };
+    if start > end {
+        return None;
+    }
+    Some(start..=end)
+}
+
+/// Element types that can be filtered.
+///
+/// This trait is sealed, it is implemented for the 8, 16, 32 and 64-bit integers, `f32`
+/// and `f64`. Whatever the element type, the emitted ids are `u32`.
+///
+/// Ranges can be expressed with any [`RangeBounds`]. NaN is never part of a range,
+/// not even of `..`.
+pub trait FilterElement: avx2::Avx2Element + avx512::Avx512Element {}
+
+impl FilterElement for u8 {}
+impl FilterElement for i8 {}
+impl FilterElement for u16 {}
+impl FilterElement for i16 {}
+impl FilterElement for u32 {}
+impl FilterElement for i32 {}
+impl FilterElement for u64 {}
+impl FilterElement for i64 {}
+impl FilterElement for f32 {}
+impl FilterElement for f64 {}
+
+// -------------------------------------------------------------------------------------------
+// Runtime dispatch
+
+/// Kernel family used by [`filter_vec`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Backend {
+    /// The [`avx512`] kernels. Needs AVX-512F, AVX-512BW and POPCNT.
+    Avx512,
+    /// The [`avx2`] kernels. Needs AVX2 and POPCNT.
+    Avx2,
+    /// The portable [`scalar`] kernels, used when neither of the above is available.
+    Scalar,
+}
+
+const BACKEND_UNKNOWN: u8 = 0;
+const BACKEND_AVX512: u8 = 1;
+const BACKEND_AVX2: u8 = 2;
+const BACKEND_SCALAR: u8 = 3;
+
+static DETECTED_BACKEND: AtomicU8 = AtomicU8::new(BACKEND_UNKNOWN);
+
+fn detect_backend() -> Backend {
+    // The SIMD counting kernels also need POPCNT, which comes with AVX2 on every CPU.
+    if !is_x86_feature_detected!("popcnt") {
+        Backend::Scalar
+    } else if avx512::is_available() {
+        Backend::Avx512
+    } else if is_x86_feature_detected!("avx2") {
+        Backend::Avx2
+    } else {
+        Backend::Scalar
+    }
+}
+
+/// Returns the best backend supported by the running CPU.
+///
+/// CPU features are only probed on the first call, the result is cached afterwards.
+pub fn backend() -> Backend {
+    match DETECTED_BACKEND.load(Ordering::Relaxed) {
+        BACKEND_AVX512 => Backend::Avx512,
+        BACKEND_AVX2 => Backend::Avx2,
+        BACKEND_SCALAR => Backend::Scalar,
+        _ => {
+            let backend = detect_backend();
+            let code = match backend {
+                Backend::Avx512 => BACKEND_AVX512,
+                Backend::Avx2 => BACKEND_AVX2,
+                Backend::Scalar => BACKEND_SCALAR,
+            };
+            DETECTED_BACKEND.store(code, Ordering::Relaxed);
+            backend
+        }
+    }
+}
+
+/// Writes in `output` the ids of the elements of `input` that belong to `range`.
+///
+/// Dispatches to the fastest kernel available on the running CPU, and falls back
+/// to the branchless [`scalar`] kernel when no SIMD instruction set is available.
+pub fn filter_vec<T: FilterElement>(input: &[T], range: impl RangeBounds<T>, output: &mut Vec<u32>) {
+    filter_vec_with_offset(input, range, 0, output);
+}
+
+/// Same as [`filter_vec`], but the id of `input[0]` is `first_id` instead of 0.
+///
+/// This is handy to filter a column block by block.
+///
+/// # Panics
+///
+/// Panics if the id of the last element, `first_id + input.len() - 1`, does not fit in a `u32`.
+pub fn filter_vec_with_offset<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    first_id: u32,
+    output: &mut Vec<u32>,
+) {
+    match backend() {
+        Backend::Avx512 => avx512::filter_vec_with_offset(input, range, first_id, output),
+        Backend::Avx2 => avx2::filter_vec_with_offset(input, range, first_id, output),
+        Backend::Scalar => scalar::filter_vec_with_offset(input, range, first_id, output),
+    }
+}
+
+/// Same as [`filter_vec_with_offset`], but the ids are appended after the current
+/// content of `output` instead of replacing it.
+///
+/// The existing ids are kept, so matches from several column chunks can be
+/// accumulated in the same `Vec`.
+///
+/// # Panics
+///
+/// Panics if the id of the last element, `first_id + input.len() - 1`, does not fit in a `u32`.
+pub fn filter_vec_append<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    first_id: u32,
+    output: &mut Vec<u32>,
+) {
+    match backend() {
+        Backend::Avx512 => avx512::filter_vec_append(input, range, first_id, output),
+        Backend::Avx2 => avx2::filter_vec_append(input, range, first_id, output),
+        Backend::Scalar => scalar::filter_vec_append(input, range, first_id, output),
+    }
+}
+
+/// Writes at the start of `output` the ids of the elements of `input` that belong to
+/// `range`, and returns their number.
+///
+/// This is [`filter_vec`] for callers that manage their own memory: the first
+/// returned-number slots of `output` are initialized, in order.
+///
+/// `output` must have room for `input.len()` ids, the worst case where every element
+/// matches. The SIMD kernels store a full register of ids at a time, so the slots
+/// past the returned number may be overwritten too, but never past `input.len()`:
+/// no extra padding is needed.
+///
+/// # Panics
+///
+/// Panics if `output` is shorter than `input`.
+pub fn filter_vec_into_slice<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    output: &mut [MaybeUninit<u32>],
+) -> usize {
+    match backend() {
+        Backend::Avx512 => avx512::filter_vec_into_slice(input, range, output),
+        Backend::Avx2 => avx2::filter_vec_into_slice(input, range, output),
+        Backend::Scalar => scalar::filter_vec_into_slice(input, range, output),
+    }
+}
+
+/// Calls `visit` with the ids of the elements of `input` that belong to `range`, block
+/// by block.
+///
+/// This is [`filter_vec`] for consumers that process ids as they come, like collectors:
+/// the input is filtered 512 elements at a time into a buffer on the stack, and `visit`
+/// is called with the ids of each block, in order. Blocks without matches are skipped,
+/// so `visit` is never called with an empty slice.
+///
+/// # Panics
+///
+/// Panics if the ids of `input` do not fit in a `u32`.
+pub fn filter_vec_visit<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    visit: impl FnMut(&[u32]),
+) {
+    match backend() {
+        Backend::Avx512 => avx512::filter_vec_visit(input, range, visit),
+        Backend::Avx2 => avx2::filter_vec_visit(input, range, visit),
+        Backend::Scalar => scalar::filter_vec_visit(input, range, visit),
+    }
+}
+
+/// Sets bit `i` of `bitset` if `input[i]` belongs to `range`, and clears it otherwise.
+///
+/// Bit `i` is bit `i % 64` of `bitset[i / 64]`. The first `input.len().div_ceil(64)`
+/// words of `bitset` are overwritten, the bits past `input.len()` are cleared and the
+/// following words are left untouched.
+///
+/// # Panics
+///
+/// Panics if `bitset` has less than `input.len().div_ceil(64)` words.
+pub fn filter_to_bitset<T: FilterElement>(input: &[T], range: impl RangeBounds<T>, bitset: &mut [u64]) {
+    match backend() {
+        Backend::Avx512 => avx512::filter_to_bitset(input, range, bitset),
+        Backend::Avx2 => avx2::filter_to_bitset(input, range, bitset),
+        Backend::Scalar => scalar::filter_to_bitset(input, range, bitset),
+    }
+}
+
+/// Returns the number of elements of `input` that belong to `range`.
+///
+/// Unlike [`filter_vec`], no id is materialized.
+pub fn count_in_range<T: FilterElement>(input: &[T], range: impl RangeBounds<T>) -> usize {
+    match backend() {
+        Backend::Avx512 => avx512::count_in_range(input, range),
+        Backend::Avx2 => avx2::count_in_range(input, range),
+        Backend::Scalar => scalar::count_in_range(input, range),
+    }
+}
+
+/// Writes in `output` the ids of the matches of `range` in `input`, skipping the
+/// first `skip` matches and keeping at most `limit` of them.
+///
+/// This is `filter_vec` followed by `output.drain(..skip)` and `output.truncate(limit)`,
+/// but the scan stops as soon as `skip + limit` matches are found, and only room for
+/// `limit` ids is reserved.
+pub fn filter_vec_limit<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    skip: usize,
+    limit: usize,
+    output: &mut Vec<u32>,
+) {
+    match backend() {
+        Backend::Avx512 => avx512::filter_vec_limit(input, range, skip, limit, output),
+        Backend::Avx2 => avx2::filter_vec_limit(input, range, skip, limit, output),
+        Backend::Scalar => scalar::filter_vec_limit(input, range, skip, limit, output),
+    }
+}
+
+/// Returns true if at least one element of `input` belongs to `range`.
+///
+/// The scan stops at the first match.
+pub fn any_in_range<T: FilterElement>(input: &[T], range: impl RangeBounds<T>) -> bool {
+    match backend() {
+        Backend::Avx512 => avx512::any_in_range(input, range),
+        Backend::Avx2 => avx2::any_in_range(input, range),
+        Backend::Scalar => scalar::any_in_range(input, range),
+    }
+}
+
+/// Returns the position of the first element of `input` that belongs to `range`, or
+/// `None` if there is none.
+///
+/// The scan stops at the first match.
+pub fn position_first_in_range<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+) -> Option<usize> {
+    match backend() {
+        Backend::Avx512 => avx512::position_first_in_range(input, range),
+        Backend::Avx2 => avx2::position_first_in_range(input, range),
+        Backend::Scalar => scalar::position_first_in_range(input, range),
+    }
+}
+
+/// Returns the position of the last element of `input` that belongs to `range`, or
+/// `None` if there is none.
+///
+/// `input` is scanned backwards, and the scan stops at the first match.
+pub fn position_last_in_range<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+) -> Option<usize> {
+    match backend() {
+        Backend::Avx512 => avx512::position_last_in_range(input, range),
+        Backend::Avx2 => avx2::position_last_in_range(input, range),
+        Backend::Scalar => scalar::position_last_in_range(input, range),
+    }
+}
+
+/// Writes in `output` the ids of the elements of `input` equal to `value`.
+///
+/// This is cheaper than filtering on `value..=value`: a single comparison is
+/// run per element. As with `==`, NaN is never matched.
+pub fn filter_eq<T: FilterElement>(input: &[T], value: T, output: &mut Vec<u32>) {
+    match backend() {
+        Backend::Avx512 => avx512::filter_eq(input, value, output),
+        Backend::Avx2 => avx2::filter_eq(input, value, output),
+        Backend::Scalar => scalar::filter_eq(input, value, output),
+    }
+}
 
-use std::ops::RangeInclusive;
+/// Writes in `output` the ids of the elements of `input` different from `value`.
+///
+/// As with `!=`, NaN elements are always matched.
+pub fn filter_ne<T: FilterElement>(input: &[T], value: T, output: &mut Vec<u32>) {
+    match backend() {
+        Backend::Avx512 => avx512::filter_ne(input, value, output),
+        Backend::Avx2 => avx2::filter_ne(input, value, output),
+        Backend::Scalar => scalar::filter_ne(input, value, output),
+    }
+}
+
+/// Writes in `output` the ids of the elements of `input` that belong to at least one of `ranges`.
+///
+/// The ranges may overlap and be in any order. Every id is written once, and
+/// the output is sorted, as with [`filter_vec`].
+pub fn filter_vec_multi_range<T: FilterElement>(
+    input: &[T],
+    ranges: &[RangeInclusive<T>],
+    output: &mut Vec<u32>,
+) {
+    match backend() {
+        Backend::Avx512 => avx512::filter_vec_multi_range(input, ranges, output),
+        Backend::Avx2 => avx2::filter_vec_multi_range(input, ranges, output),
+        Backend::Scalar => scalar::filter_vec_multi_range(input, ranges, output),
+    }
+}
+
+/// Writes in `output` the ids of the elements of `input` that do not belong to `range`.
+///
+/// This is the complement of [`filter_vec`]: NaN elements, which belong to no
+/// range, are always matched, and an empty range matches every element.
+pub fn filter_vec_outside<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    output: &mut Vec<u32>,
+) {
+    match backend() {
+        Backend::Avx512 => avx512::filter_vec_outside(input, range, output),
+        Backend::Avx2 => avx2::filter_vec_outside(input, range, output),
+        Backend::Scalar => scalar::filter_vec_outside(input, range, output),
+    }
+}
+
+/// Writes in `output` the ids of the elements of `input` that are equal to one of `values`.
+///
+/// `values` may be in any order and contain duplicates. The lookup strategy
+/// depends on the set: a handful of values are compared against every element,
+/// a dense set over a small domain is turned into a bitmap, and other sets are
+/// binary searched.
+pub fn filter_in_set(input: &[u32], values: &[u32], output: &mut Vec<u32>) {
+    match backend() {
+        Backend::Avx512 => avx512::filter_in_set(input, values, output),
+        Backend::Avx2 => avx2::filter_in_set(input, values, output),
+        Backend::Scalar => scalar::filter_in_set(input, values, output),
+    }
+}
+
+/// Writes in `output` the elements of `input` that belong to `range`, in order.
+///
+/// This is the values counterpart of [`filter_vec`], handy to feed an aggregation.
+pub fn filter_values<T: FilterElement>(input: &[T], range: impl RangeBounds<T>, output: &mut Vec<T>) {
+    match backend() {
+        Backend::Avx512 => avx512::filter_values(input, range, output),
+        Backend::Avx2 => avx2::filter_values(input, range, output),
+        Backend::Scalar => scalar::filter_values(input, range, output),
+    }
+}
+
+/// Writes in `ids` the ids of the elements of `input` that belong to `range`, and
+/// in `values` the elements themselves.
+///
+/// Both outputs are computed in a single pass: `values[i]` is `input[ids[i]]`.
+pub fn filter_ids_and_values<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    ids: &mut Vec<u32>,
+    values: &mut Vec<T>,
+) {
+    match backend() {
+        Backend::Avx512 => avx512::filter_ids_and_values(input, range, ids, values),
+        Backend::Avx2 => avx2::filter_ids_and_values(input, range, ids, values),
+        Backend::Scalar => scalar::filter_ids_and_values(input, range, ids, values),
+    }
+}
+
+/// Removes from `values` the elements that do not belong to `range`, keeping the
+/// order of the others.
+///
+/// This is `values.retain(|v| range.contains(v))`, compacted in place with the SIMD
+/// kernels of [`filter_values`]. The capacity of `values` is left untouched.
+pub fn retain_in_range<T: FilterElement>(values: &mut Vec<T>, range: impl RangeBounds<T>) {
+    match backend() {
+        Backend::Avx512 => avx512::retain_in_range(values, range),
+        Backend::Avx2 => avx2::retain_in_range(values, range),
+        Backend::Scalar => scalar::retain_in_range(values, range),
+    }
+}
+
+/// Writes in `inside` the ids of the elements of `input` that belong to `range`, and
+/// in `outside` the ids of the others, in a single pass.
+///
+/// `inside` is the output of [`filter_vec`], and `outside` the output of
+/// [`filter_vec_outside`].
+pub fn partition_by_range<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    inside: &mut Vec<u32>,
+    outside: &mut Vec<u32>,
+) {
+    match backend() {
+        Backend::Avx512 => avx512::partition_by_range(input, range, inside, outside),
+        Backend::Avx2 => avx2::partition_by_range(input, range, inside, outside),
+        Backend::Scalar => scalar::partition_by_range(input, range, inside, outside),
+    }
+}
+
+/// Writes in `output` the ids of `candidate_ids` whose value in `column` belongs to `range`.
+///
+/// This refines the output of a previous filter with a predicate on another column:
+/// only the values at the candidate ids are loaded. The candidates are kept in
+/// order, so if `candidate_ids` is sorted, so is `output`.
+///
+/// # Panics
+///
+/// Panics if a candidate id is out of the bounds of `column`.
+pub fn filter_selection(
+    column: &[u32],
+    candidate_ids: &[u32],
+    range: impl RangeBounds<u32>,
+    output: &mut Vec<u32>,
+) {
+    match backend() {
+        Backend::Avx512 => avx512::filter_selection(column, candidate_ids, range, output),
+        Backend::Avx2 => avx2::filter_selection(column, candidate_ids, range, output),
+        Backend::Scalar => scalar::filter_selection(column, candidate_ids, range, output),
+    }
+}
+
+/// Returns the `n` lowest bits set in `bitset`, or all of them if there are less than `n`.
+pub(crate) fn lowest_set_bits(mut bitset: u64, n: usize) -> u64 {
+    let mut lowest_bits = 0;
+    for _ in 0..n {
+        if bitset == 0 {
+            break;
+        }
+        let lowest_bit = bitset & bitset.wrapping_neg();
+        lowest_bits |= lowest_bit;
+        bitset ^= lowest_bit;
+    }
+    lowest_bits
+}
+
+/// Number of elements filtered between two reservations of room in the output `Vec`.
+///
+/// Room is reserved for one block at a time rather than for the whole input, so that
+/// the memory held by the output tracks the number of matches. It is a multiple of
+/// every `NUM_LANES`, so only the last block has a partial word.
+pub(crate) const OUTPUT_BLOCK_LEN: usize = 4096;
+
+/// Number of elements filtered between two calls of the visitor of [`filter_vec_visit`],
+/// and between two refills of the ids of a [`RangeFilterIter`].
+///
+/// Their ids fit in a small buffer, on the stack for the visitor, and the iterator does
+/// not compute them far ahead of their use. It is a multiple of every `NUM_LANES`, so
+/// only the last block has a partial word.
+pub(crate) const VISIT_BLOCK_LEN: usize = 512;
+
+/// Panics if an output of `output_len` slots cannot hold the ids of `len` elements.
+pub(crate) fn assert_output_fits(len: usize, output_len: usize) {
+    assert!(
+        len <= output_len,
+        "an output of {output_len} slots cannot hold the ids of {len} elements"
+    );
+}
+
+/// Panics if a bitset of `bitset_len` words cannot hold `len` bits.
+pub(crate) fn assert_bitset_fits(len: usize, bitset_len: usize) {
+    assert!(
+        len.div_ceil(64) <= bitset_len,
+        "a bitset of {bitset_len} words cannot hold {len} bits"
+    );
+}
+
+/// Panics if one of `ids` is not lower than `len`.
+pub(crate) fn assert_ids_in_bounds(ids: &[u32], len: usize) {
+    if let Some(&max_id) = ids.iter().max() {
+        assert!(
+            (max_id as usize) < len,
+            "id {max_id} is out of bounds for a column of {len} elements"
+        );
+    }
+}
+
+/// Panics if the ids `first_id..first_id + len` do not all fit in a `u32`.
+pub(crate) fn assert_ids_fit(first_id: u32, len: usize) {
+    let num_available_ids = (u32::MAX - first_id) as u64 + 1;
+    assert!(
+        len as u64 <= num_available_ids,
+        "ids starting at {first_id} overflow u32 for {len} elements"
+    );
+}
 
 // -------------------------------------------------------------------------------------------
 // Scalar version with a for-loop
 
-pub fn filter_vec_scalar(input: &[u32], range: RangeInclusive<u32>, output: &mut Vec<u32>) {
+#[allow(clippy::needless_range_loop)]
+pub fn filter_vec_scalar<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    output: &mut Vec<u32>,
+) {
     output.clear();
-    output.reserve(input.len());
-    for i in 0..input.len() {
-        if range.contains(&input[i]) {
-            output.push(i as u32);
+    let Some(range) = to_inclusive(range) else {
+        return;
+    };
+    for block_start in (0..input.len()).step_by(OUTPUT_BLOCK_LEN) {
+        let block_end = input.len().min(block_start + OUTPUT_BLOCK_LEN);
+        output.reserve(block_end - block_start);
+        for i in block_start..block_end {
+            if range.contains(&input[i]) {
+                output.push(i as u32);
+            }
         }
     }
 }
@@ -34,36 +633,75 @@ pub fn filter_vec_scalar(input: &[u32], range: RangeInclusive<u32>, output: &mut
 // -------------------------------------------------------------------------------------------
 // Branchless Scalar version
 
-pub fn filter_vec_nobranch(input: &[u32], range: RangeInclusive<u32>, output: &mut Vec<u32>) {
+pub fn filter_vec_nobranch<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    output: &mut Vec<u32>,
+) {
     output.clear();
-    output.resize(input.len(), 0u32);
-    let mut output_len = 0;
-    for (id, &el) in input.iter().enumerate() {
-        output[output_len] = id as u32;
-        output_len += if range.contains(&el) { 1 } else { 0 };
-    }
-    output.truncate(output_len);
+    let Some(range) = to_inclusive(range) else {
+        return;
+    };
+    scalar::append_ids(input, |el| range.contains(&el), 0, output);
 }
 
 // -------------------------------------------------------------------------------------------
 // Iterator version
 
-pub fn filter_vec_iter(input: &[u32], range: RangeInclusive<u32>, output: &mut Vec<u32>) {
+pub fn filter_vec_iter<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    output: &mut Vec<u32>,
+) {
     output.clear();
-    output.reserve(input.len());
-    output.extend(
-        input
-            .iter()
-            .enumerate()
-            .filter(|&(_, el)| range.contains(el))
-            .map(|(id, _)| id as u32),
-    );
+    let Some(range) = to_inclusive(range) else {
+        return;
+    };
+    for (block_id, block) in input.chunks(OUTPUT_BLOCK_LEN).enumerate() {
+        output.reserve(block.len());
+        let block_first_id = (block_id * OUTPUT_BLOCK_LEN) as u32;
+        output.extend(
+            block
+                .iter()
+                .enumerate()
+                .filter(|&(_, el)| range.contains(el))
+                .map(|(id, _)| block_first_id + id as u32),
+        );
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Runs `$check` once per backend available on the running CPU, the dispatching
+    /// crate root included, with `$backend` naming the module of the backend.
+    macro_rules! for_each_backend {
+        (|$backend:ident| $check:block) => {{
+            {
+                use crate as $backend;
+                $check
+            }
+            {
+                use crate::scalar as $backend;
+                $check
+            }
+            if is_x86_feature_detected!("avx2") {
+                use crate::avx2 as $backend;
+                $check
+            }
+            if avx512::is_available() {
+                use crate::avx512 as $backend;
+                $check
+            }
+        }};
+    }
+
+    /// Input lengths the per-backend tests cut their inputs to: empty, shorter
+    /// than a word, around the word boundaries of the narrowest lanes, and
+    /// several words followed by a partial one.
+    const TEST_LENS: [usize; 10] = [0, 1, 7, 8, 9, 31, 64, 65, 129, 300];
+
     #[test]
     fn test_filter() {
         let v = &[14, 3, 15, 3, 5, 14, 2, 3, 2, 3, 3, 4, 6, 10, 3, 7];
@@ -75,7 +713,7 @@ mod tests {
             .filter(|&(_, el)| interval.contains(&el))
             .map(|(ord, _)| ord as u32)
             .collect();
-        {
+        if is_x86_feature_detected!("avx2") {
             let mut output = Vec::new();
             super::avx2::filter_vec(&v[..], interval.clone(), &mut output);
             assert_eq!(&output[..], &expected);
@@ -90,10 +728,760 @@ mod tests {
             filter_vec_iter(&v[..], interval.clone(), &mut output);
             assert_eq!(&output[..], &expected);
         }
-    //    {
-    //         let mut output = Vec::new();
-    //         super::avx512::filter_vec(&v[..], interval.clone(), &mut output);
-    //         assert_eq!(&output[..], &expected);
-    //     }
+        {
+            let mut output = Vec::new();
+            filter_vec(&v[..], interval.clone(), &mut output);
+            assert_eq!(&output[..], &expected);
+        }
+        if avx512::is_available() {
+            let mut output = Vec::new();
+            super::avx512::filter_vec(&v[..], interval.clone(), &mut output);
+            assert_eq!(&output[..], &expected);
+        }
+    }
+
+    #[test]
+    fn test_filter_full_u32_domain() {
+        let v: Vec<u32> = (0..37u32)
+            .map(|i| i.wrapping_mul(0x9E37_79B9))
+            .chain([0, 1, i32::MAX as u32, 1 << 31, u32::MAX - 1, u32::MAX])
+            .collect();
+        let ranges = [
+            0..=u32::MAX,
+            0..=(1 << 31),
+            (i32::MAX as u32)..=(1 << 31),
+            (1 << 31)..=u32::MAX,
+            (u32::MAX - 1)..=u32::MAX,
+            0x1000_0000..=0xC000_0000,
+        ];
+        for range in ranges {
+            let mut expected = Vec::new();
+            filter_vec_scalar(&v, range.clone(), &mut expected);
+            let mut output = Vec::new();
+            for_each_backend!(|backend| {
+                backend::filter_vec(&v, range.clone(), &mut output);
+                assert_eq!(output, expected);
+            });
+        }
+    }
+
+    #[test]
+    fn test_filter_any_length() {
+        let v: Vec<u32> = (0..150u32).map(|i| (i * 13) % 17).collect();
+        for len in 0..v.len() {
+            check_all_backends(&v[..len], 4..=9);
+        }
+    }
+
+    #[test]
+    fn test_filter_with_offset() {
+        let v: Vec<u32> = (0..45u32).map(|i| (i * 13) % 17).collect();
+        let mut expected = Vec::new();
+        filter_vec_scalar(&v, 3..=11, &mut expected);
+        for first_id in [0, 1, 1000, u32::MAX - 44] {
+            let expected: Vec<u32> = expected.iter().map(|&id| id + first_id).collect();
+            let mut output = Vec::new();
+            for_each_backend!(|backend| {
+                backend::filter_vec_with_offset(&v, 3..=11, first_id, &mut output);
+                assert_eq!(output, expected);
+            });
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "overflow")]
+    fn test_filter_with_offset_overflow() {
+        let v = [0u32; 10];
+        let mut output = Vec::new();
+        filter_vec_with_offset(&v, 0..=1, u32::MAX - 8, &mut output);
+    }
+
+    #[test]
+    fn test_filter_append() {
+        let v: Vec<u32> = (0..100u32).map(|i| (i * 13) % 17).collect();
+        let mut expected = Vec::new();
+        filter_vec_scalar(&v, 3..=11, &mut expected);
+        for_each_backend!(|backend| {
+            let mut output = Vec::new();
+            for (chunk_id, chunk) in v.chunks(23).enumerate() {
+                backend::filter_vec_append(chunk, 3..=11, chunk_id as u32 * 23, &mut output);
+            }
+            assert_eq!(output, expected);
+        });
+    }
+
+    fn check_bitset_all_backends<T: FilterElement>(
+        input: &[T],
+        range: RangeInclusive<T>,
+        expected_ids: &[u32],
+    ) {
+        let num_u64 = input.len().div_ceil(64);
+        let mut expected = vec![0u64; num_u64 + 1];
+        for &id in expected_ids {
+            expected[id as usize / 64] |= 1 << (id % 64);
+        }
+        // The word past the bitset must be left untouched.
+        expected[num_u64] = u64::MAX;
+        for_each_backend!(|backend| {
+            let mut bitset = vec![u64::MAX; num_u64 + 1];
+            backend::filter_to_bitset(input, range.clone(), &mut bitset);
+            assert_eq!(bitset, expected);
+        });
+    }
+
+    fn check_count_all_backends<T: FilterElement>(
+        input: &[T],
+        range: RangeInclusive<T>,
+        expected: usize,
+    ) {
+        for_each_backend!(|backend| {
+            assert_eq!(backend::count_in_range(input, range.clone()), expected);
+        });
+    }
+
+    #[test]
+    fn test_count_in_range() {
+        check_count_all_backends::<u8>(&[], 0..=u8::MAX, 0);
+        check_count_all_backends::<u32>(&[], 0..=u32::MAX, 0);
+        check_count_all_backends::<i64>(&[], i64::MIN..=i64::MAX, 0);
+        // None of these lengths is a multiple of a lane width, so every input ends
+        // with a partial word.
+        for len in [1, 5, 13, 67, 131] {
+            let bytes: Vec<u8> = (0..len).map(|i| (i * 37) as u8).collect();
+            let expected = bytes.iter().filter(|&&el| (40..=200).contains(&el)).count();
+            check_count_all_backends(&bytes, 40..=200, expected);
+            check_count_all_backends(&bytes, 0..=u8::MAX, len);
+            let words: Vec<u32> = bytes.iter().map(|&el| el as u32).collect();
+            check_count_all_backends(&words, 40..=200, expected);
+            check_count_all_backends(&words, 0..=u32::MAX, len);
+            let longs: Vec<i64> = bytes.iter().map(|&el| el as i64 - 100).collect();
+            check_count_all_backends(&longs, -60..=100, expected);
+            check_count_all_backends(&longs, i64::MIN..=i64::MAX, len);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot hold")]
+    fn test_filter_to_bitset_too_small() {
+        let v = [0u32; 65];
+        let mut bitset = [0u64; 1];
+        filter_to_bitset(&v, 0..=1, &mut bitset);
+    }
+
+    fn check_range_bounds<T: FilterElement, R: RangeBounds<T> + Clone>(
+        input: &[T],
+        range: R,
+        predicate: impl Fn(T) -> bool,
+    ) {
+        let expected: Vec<u32> = (0..input.len() as u32)
+            .filter(|&id| predicate(input[id as usize]))
+            .collect();
+        let mut output = vec![7];
+        filter_vec_scalar(input, range.clone(), &mut output);
+        assert_eq!(output, expected);
+        filter_vec_nobranch(input, range.clone(), &mut output);
+        assert_eq!(output, expected);
+        filter_vec_iter(input, range.clone(), &mut output);
+        assert_eq!(output, expected);
+        assert_eq!(count_in_range(input, range.clone()), expected.len());
+        for_each_backend!(|backend| {
+            output.push(7);
+            backend::filter_vec(input, range.clone(), &mut output);
+            assert_eq!(output, expected);
+        });
+        let mut bitset = vec![u64::MAX; input.len().div_ceil(64)];
+        filter_to_bitset(input, range, &mut bitset);
+        let num_ones: u32 = bitset.iter().map(|bits| bits.count_ones()).sum();
+        assert_eq!(num_ones as usize, expected.len());
+    }
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn test_filter_range_bounds() {
+        use std::ops::Bound::{Excluded, Included, Unbounded};
+        let v: Vec<u8> = (0..=255u8).chain((0..=255u8).rev()).collect();
+        check_range_bounds(&v, .., |_| true);
+        check_range_bounds(&v, ..10, |x| x < 10);
+        check_range_bounds(&v, ..=10, |x| x <= 10);
+        check_range_bounds(&v, 7.., |x| x >= 7);
+        check_range_bounds(&v, 7..200, |x| (7..200).contains(&x));
+        check_range_bounds(&v, 5..0, |_| false);
+        check_range_bounds(&v, 0..0, |_| false);
+        check_range_bounds(&v, 9..=3, |_| false);
+        check_range_bounds(&v, ..0, |_| false);
+        check_range_bounds(&v, (Excluded(255), Unbounded), |_| false);
+        check_range_bounds(&v, (Excluded(3), Included(4)), |x| x == 4);
+        check_range_bounds(&v, (Excluded(3), Excluded(4)), |_| false);
+        let signed: Vec<i64> = v.iter().map(|&x| x as i64 * 1000 - 100_000).collect();
+        check_range_bounds(&signed, ..-50_000, |x| x < -50_000);
+        check_range_bounds(&signed, (Excluded(i64::MIN), Excluded(0)), |x| x < 0);
+        check_range_bounds(&signed, (Excluded(i64::MAX), Unbounded), |_| false);
+        let mut floats: Vec<f32> = v.iter().map(|&x| x as f32 / 4.0 - 20.0).collect();
+        floats[3] = f32::NAN;
+        floats[5] = f32::INFINITY;
+        floats[300] = f32::NEG_INFINITY;
+        check_range_bounds(&floats, .., |x| !x.is_nan());
+        check_range_bounds(&floats, ..0.0, |x| x < 0.0);
+        check_range_bounds(&floats, (Excluded(0.0), Unbounded), |x| x > 0.0);
+        check_range_bounds(&floats, (Excluded(-0.0), Excluded(10.0)), |x| x > 0.0 && x < 10.0);
+        check_range_bounds(&floats, (Excluded(f32::INFINITY), Unbounded), |_| false);
+        check_range_bounds(&floats, 1.0..1.0, |_| false);
+    }
+
+    fn check_eq_all_backends<T: FilterElement>(input: &[T], value: T) {
+        let expected_eq: Vec<u32> = (0..input.len() as u32)
+            .filter(|&id| input[id as usize] == value)
+            .collect();
+        let expected_ne: Vec<u32> = (0..input.len() as u32)
+            .filter(|&id| input[id as usize] != value)
+            .collect();
+        let mut output = Vec::new();
+        for_each_backend!(|backend| {
+            backend::filter_eq(input, value, &mut output);
+            assert_eq!(output, expected_eq);
+            backend::filter_ne(input, value, &mut output);
+            assert_eq!(output, expected_ne);
+        });
+    }
+
+    #[test]
+    fn test_filter_eq_ne() {
+        let ords: Vec<u32> = (0..300u32).map(|i| (i * 7) % 13).collect();
+        for len in TEST_LENS {
+            let ords = &ords[..len];
+            check_eq_all_backends(ords, 3);
+            check_eq_all_backends(ords, 42);
+            let ords_64: Vec<i64> = ords.iter().map(|&v| v as i64 - 6).collect();
+            check_eq_all_backends(&ords_64, -2);
+            let ords_16: Vec<u16> = ords.iter().map(|&v| v as u16 + 0x8000).collect();
+            check_eq_all_backends(&ords_16, 0x8005);
+            let ords_8: Vec<i8> = ords.iter().map(|&v| v as i8 - 100).collect();
+            check_eq_all_backends(&ords_8, -88);
+            let mut floats: Vec<f64> = ords.iter().map(|&v| v as f64 / 2.0).collect();
+            if len > 5 {
+                floats[5] = f64::NAN;
+            }
+            check_eq_all_backends(&floats, 1.5);
+            check_eq_all_backends(&floats, f64::NAN);
+            let floats_32: Vec<f32> = floats.iter().map(|&v| v as f32).collect();
+            check_eq_all_backends(&floats_32, 0.0);
+        }
+    }
+
+    fn check_multi_range_all_backends<T: FilterElement>(input: &[T], ranges: &[RangeInclusive<T>]) {
+        let expected: Vec<u32> = (0..input.len() as u32)
+            .filter(|&id| ranges.iter().any(|range| range.contains(&input[id as usize])))
+            .collect();
+        let mut output = Vec::new();
+        for_each_backend!(|backend| {
+            backend::filter_vec_multi_range(input, ranges, &mut output);
+            assert_eq!(output, expected);
+        });
+    }
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn test_filter_vec_multi_range() {
+        let input: Vec<u32> = (0..300u32).map(|i| i.wrapping_mul(2_654_435_761) >> 24).collect();
+        for len in TEST_LENS {
+            let input = &input[..len];
+            check_multi_range_all_backends(input, &[]);
+            check_multi_range_all_backends(input, &[0..=10, 100..=200]);
+            check_multi_range_all_backends(input, &[150..=250, 100..=200, 20..=10]);
+            check_multi_range_all_backends(input, &[5..=5, 255..=u32::MAX, 0..=0]);
+            // Overlapping sub-ranges: partly, nested and repeated.
+            check_multi_range_all_backends(input, &[0..=120, 100..=200, 150..=250]);
+            check_multi_range_all_backends(input, &[0..=u32::MAX, 10..=20]);
+            check_multi_range_all_backends(input, &[64..=128, 64..=128, 100..=110]);
+            // Empty sub-ranges only, and between non-empty ones.
+            check_multi_range_all_backends(input, &[10..=5, u32::MAX..=0]);
+            check_multi_range_all_backends(input, &[0..=50, 200..=100, 150..=255]);
+            let input_64: Vec<i64> = input.iter().map(|&v| v as i64 - 128).collect();
+            check_multi_range_all_backends(&input_64, &[i64::MIN..=-100, -10..=10, 100..=i64::MAX]);
+            check_multi_range_all_backends(&input_64, &[-50..=50, 0..=100, i64::MAX..=i64::MIN]);
+            let input_8: Vec<u8> = input.iter().map(|&v| v as u8).collect();
+            check_multi_range_all_backends(&input_8, &[0..=10, 100..=200, 250..=255]);
+            check_multi_range_all_backends(&input_8, &[0..=200, 100..=255, 20..=10]);
+            let floats: Vec<f32> = input.iter().map(|&v| v as f32 / 4.0).collect();
+            check_multi_range_all_backends(&floats, &[0.5..=2.0, 30.0..=f32::INFINITY, f32::NAN..=1.0]);
+            check_multi_range_all_backends(&floats, &[1.0..=20.0, 10.0..=40.0, 5.0..=-5.0]);
+        }
+    }
+
+    fn check_partition_all_backends<T: FilterElement>(
+        input: &[T],
+        range: impl RangeBounds<T> + Clone,
+        expected_outside: &[u32],
+    ) {
+        let mut expected_inside = Vec::new();
+        filter_vec_scalar(input, range.clone(), &mut expected_inside);
+        let (mut inside, mut outside) = (Vec::new(), Vec::new());
+        for_each_backend!(|backend| {
+            backend::partition_by_range(input, range.clone(), &mut inside, &mut outside);
+            assert_eq!((&inside[..], &outside[..]), (&expected_inside[..], expected_outside));
+        });
+    }
+
+    fn check_outside_all_backends<T: FilterElement>(input: &[T], range: impl RangeBounds<T> + Clone) {
+        // Unlike `RangeFull::contains`, NaN belongs to no range.
+        let inclusive = to_inclusive(range.clone());
+        let expected: Vec<u32> = (0..input.len() as u32)
+            .filter(|&id| !inclusive.as_ref().is_some_and(|range| range.contains(&input[id as usize])))
+            .collect();
+        check_partition_all_backends(input, range.clone(), &expected);
+        let mut output = Vec::new();
+        for_each_backend!(|backend| {
+            backend::filter_vec_outside(input, range.clone(), &mut output);
+            assert_eq!(output, expected);
+        });
+    }
+
+    /// Checks that nothing but NaN lies outside of the full domain of `T`.
+    fn check_outside_full_domain<T: FilterElement>(input: &[T]) {
+        let nan_ids: Vec<u32> = (0..input.len() as u32)
+            .filter(|&id| input[id as usize].partial_cmp(&input[id as usize]).is_none())
+            .collect();
+        check_outside_all_backends(input, T::LOWEST..=T::HIGHEST);
+        let mut output = Vec::new();
+        for_each_backend!(|backend| {
+            backend::filter_vec_outside(input, T::LOWEST..=T::HIGHEST, &mut output);
+            assert_eq!(output, nan_ids);
+        });
+    }
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn test_filter_vec_outside() {
+        let input: Vec<u32> = (0..300u32).map(|i| i.wrapping_mul(2_654_435_761)).collect();
+        for len in TEST_LENS {
+            let input = &input[..len];
+            check_outside_all_backends(input, 1 << 30..=3 << 30);
+            check_outside_all_backends(input, ..);
+            check_outside_all_backends(input, 10..10);
+            check_outside_all_backends(input, 3 << 30..=1 << 30);
+            check_outside_all_backends(input, (1 << 31)..);
+            check_outside_full_domain(input);
+            let input_16: Vec<i16> = input.iter().map(|&v| (v >> 16) as i16).collect();
+            check_outside_all_backends(&input_16, -1000..=1000);
+            check_outside_all_backends(&input_16, 1000..=-1000);
+            check_outside_full_domain(&input_16);
+            let input_64: Vec<u64> = input.iter().map(|&v| (v as u64) << 32).collect();
+            check_outside_all_backends(&input_64, ..u64::MAX / 2);
+            check_outside_all_backends(&input_64, u64::MAX..=0);
+            check_outside_full_domain(&input_64);
+            let mut floats: Vec<f64> = input.iter().map(|&v| v as f64 - 2e9).collect();
+            if len > 3 {
+                floats[3] = f64::NAN;
+            }
+            check_outside_all_backends(&floats, -1e9..=1e9);
+            check_outside_all_backends(&floats, ..);
+            check_outside_all_backends(&floats, 1e9..=-1e9);
+            check_outside_full_domain(&floats);
+        }
+    }
+
+    fn check_selection_all_backends(column: &[u32], candidate_ids: &[u32], range: RangeInclusive<u32>) {
+        let expected: Vec<u32> = candidate_ids
+            .iter()
+            .copied()
+            .filter(|&id| range.contains(&column[id as usize]))
+            .collect();
+        let mut output = Vec::new();
+        for_each_backend!(|backend| {
+            backend::filter_selection(column, candidate_ids, range.clone(), &mut output);
+            assert_eq!(output, expected);
+        });
+    }
+
+    #[test]
+    fn test_filter_selection() {
+        let column: Vec<u32> = (0..500u32).map(|i| i.wrapping_mul(2_654_435_761)).collect();
+        let mut candidate_ids = Vec::new();
+        filter_vec(&column, ..u32::MAX / 2, &mut candidate_ids);
+        candidate_ids.push(499);
+        for len in [0, 1, 7, 8, 9, 16, 17, 100, candidate_ids.len()] {
+            let candidate_ids = &candidate_ids[..len];
+            check_selection_all_backends(&column, candidate_ids, 0..=u32::MAX);
+            check_selection_all_backends(&column, candidate_ids, 1 << 30..=u32::MAX / 2);
+            check_selection_all_backends(&column, candidate_ids, u32::MAX / 2..=u32::MAX);
+        }
+        let all_ids: Vec<u32> = (0..500).collect();
+        check_selection_all_backends(&column, &all_ids, 1 << 29..=3 << 30);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_filter_selection_out_of_bounds() {
+        filter_selection(&[1, 2, 3], &[0, 3], .., &mut Vec::new());
+    }
+
+    fn check_in_set_all_backends(input: &[u32], values: &[u32]) {
+        let expected: Vec<u32> = (0..input.len() as u32)
+            .filter(|&id| values.contains(&input[id as usize]))
+            .collect();
+        let mut output = Vec::new();
+        for_each_backend!(|backend| {
+            backend::filter_in_set(input, values, &mut output);
+            assert_eq!(output, expected);
+        });
+    }
+
+    #[test]
+    fn test_filter_in_set() {
+        let input: Vec<u32> = (0..1_000u32)
+            .map(|i| i.wrapping_mul(2_654_435_761) >> (i % 24))
+            .chain([0, 1, u32::MAX, u32::MAX - 1, 1 << 31, (1 << 31) - 1])
+            .collect();
+        let tiny = [u32::MAX, 3, 0, 3];
+        let dense: Vec<u32> = (1_000..3_000).step_by(3).collect();
+        let sparse: Vec<u32> = input.iter().step_by(5).map(|&v| v ^ 1).chain([u32::MAX, 0]).collect();
+        let dense_full_span: Vec<u32> = (0..3_000).chain([u32::MAX]).collect();
+        for len in [0, 1, 7, 8, 9, 17, 100, input.len()] {
+            let input = &input[..len];
+            check_in_set_all_backends(input, &[]);
+            check_in_set_all_backends(input, &tiny);
+            check_in_set_all_backends(input, &dense);
+            check_in_set_all_backends(input, &sparse);
+            check_in_set_all_backends(input, &dense_full_span);
+            check_in_set_all_backends(input, &sparse[..9]);
+        }
+    }
+
+    fn check_values_all_backends<T: FilterElement + std::fmt::Debug>(
+        input: &[T],
+        range: RangeInclusive<T>,
+        expected_ids: &[u32],
+    ) {
+        let expected: Vec<T> = expected_ids.iter().map(|&id| input[id as usize]).collect();
+        let (mut ids, mut values) = (Vec::new(), Vec::new());
+        for_each_backend!(|backend| {
+            backend::filter_values(input, range.clone(), &mut values);
+            assert_eq!(values, expected);
+            backend::filter_ids_and_values(input, range.clone(), &mut ids, &mut values);
+            assert_eq!((&ids[..], &values[..]), (expected_ids, &expected[..]));
+        });
+        check_retain_all_backends(input, range, &expected);
+    }
+
+    fn check_retain_all_backends<T: FilterElement + std::fmt::Debug>(
+        input: &[T],
+        range: RangeInclusive<T>,
+        expected: &[T],
+    ) {
+        for_each_backend!(|backend| {
+            let mut retained = input.to_vec();
+            let capacity = retained.capacity();
+            backend::retain_in_range(&mut retained, range.clone());
+            assert_eq!(retained, expected);
+            // The elements are compacted in place, never into a new buffer.
+            assert_eq!(retained.capacity(), capacity);
+        });
+    }
+
+    /// Retains `kept` and drops `dropped` in patterns that end around the word
+    /// boundaries of both SIMD backends.
+    fn check_retain_patterns<T: FilterElement + std::fmt::Debug>(kept: T, dropped: T) {
+        let patterns: [fn(usize) -> bool; 5] = [
+            |_| true,
+            |_| false,
+            |i| i % 2 == 0,
+            |i| i % 2 == 1,
+            |i| i % 3 != 0,
+        ];
+        // Number of lanes of an AVX2 word, twice as many in an AVX-512 word.
+        let num_avx2_lanes = 32 / std::mem::size_of::<T>();
+        for num_lanes in [num_avx2_lanes, 2 * num_avx2_lanes] {
+            for len in [num_lanes - 1, num_lanes, num_lanes + 1, 3 * num_lanes + 1] {
+                for keep in patterns {
+                    let input: Vec<T> = (0..len).map(|i| if keep(i) { kept } else { dropped }).collect();
+                    let expected: Vec<T> = input.iter().copied().filter(|&v| v == kept).collect();
+                    check_retain_all_backends(&input, kept..=kept, &expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_retain_in_range_patterns() {
+        check_retain_patterns(7u8, 200);
+        check_retain_patterns(-7i8, 100);
+        check_retain_patterns(7u16, 60_000);
+        check_retain_patterns(-7i16, 300);
+        check_retain_patterns(7u32, u32::MAX);
+        check_retain_patterns(-7i32, i32::MIN);
+        check_retain_patterns(7u64, 1 << 40);
+        check_retain_patterns(-7i64, i64::MAX);
+        check_retain_patterns(0.5f32, f32::NAN);
+        check_retain_patterns(-0.5f64, f64::INFINITY);
+    }
+
+    fn check_slice_all_backends<T: FilterElement>(input: &[T], range: RangeInclusive<T>, expected: &[u32]) {
+        // One extra slot, which must be left untouched.
+        let sentinel = MaybeUninit::new(u32::MAX);
+        let mut output = vec![sentinel; input.len() + 1];
+        let check = |output: &[MaybeUninit<u32>], output_len: usize| {
+            let ids: Vec<u32> = output[..output_len].iter().map(|id| unsafe { id.assume_init() }).collect();
+            assert_eq!(ids, expected);
+            assert_eq!(unsafe { output[input.len()].assume_init() }, u32::MAX);
+        };
+        for_each_backend!(|backend| {
+            let output_len = backend::filter_vec_into_slice(input, range.clone(), &mut output[..input.len()]);
+            check(&output, output_len);
+        });
+    }
+
+    fn check_visit_all_backends<T: FilterElement>(input: &[T], range: RangeInclusive<T>, expected: &[u32]) {
+        for_each_backend!(|backend| {
+            let mut ids = Vec::new();
+            let mut last_block_id = None;
+            backend::filter_vec_visit(input, range.clone(), |block: &[u32]| {
+                // One call per block of `VISIT_BLOCK_LEN` elements with matches, in order.
+                assert!(!block.is_empty());
+                let block_id = block[0] as usize / VISIT_BLOCK_LEN;
+                assert_eq!(block[block.len() - 1] as usize / VISIT_BLOCK_LEN, block_id);
+                assert!(last_block_id < Some(block_id));
+                last_block_id = Some(block_id);
+                ids.extend_from_slice(block);
+            });
+            assert_eq!(ids, expected);
+        });
+    }
+
+    #[test]
+    fn test_filter_vec_visit_blocks() {
+        let vals: Vec<u32> = (0..3 * VISIT_BLOCK_LEN as u32 + 5).collect();
+        let mut blocks = Vec::new();
+        filter_vec_visit(&vals, 500..=1100, |ids| blocks.push(ids.to_vec()));
+        // The matches span three blocks of the input, the fourth one has none.
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks.concat(), (500..=1100).collect::<Vec<u32>>());
+        let mut num_calls = 0;
+        filter_vec_visit(&vals, 5000.., |_| num_calls += 1);
+        assert_eq!(num_calls, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot hold the ids")]
+    fn test_filter_vec_into_slice_too_small() {
+        let mut output = [MaybeUninit::uninit(); 3];
+        filter_vec_into_slice(&[1u32, 2, 3, 4], 10..=20, &mut output);
+    }
+
+    fn check_limit_all_backends<T: FilterElement>(input: &[T], range: RangeInclusive<T>, expected: &[u32]) {
+        let num_matches = expected.len();
+        let skips_and_limits = [
+            (0, 0),
+            (0, 1),
+            (0, 5),
+            (1, 3),
+            (7, 9),
+            (3, 40),
+            (0, usize::MAX),
+            (num_matches, 1),
+        ];
+        let mut output = Vec::new();
+        for (skip, limit) in skips_and_limits {
+            let expected: Vec<u32> = expected.iter().copied().skip(skip).take(limit).collect();
+            for_each_backend!(|backend| {
+                backend::filter_vec_limit(input, range.clone(), skip, limit, &mut output);
+                assert_eq!(output, expected);
+            });
+        }
+    }
+
+    #[test]
+    fn test_filter_vec_limit_capacity() {
+        let input: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+        let mut output = Vec::new();
+        filter_vec_limit(&input, 10..=200, 100, 10, &mut output);
+        assert_eq!(output, (110..120).collect::<Vec<u32>>());
+        assert!(output.capacity() < 100);
+    }
+
+    fn check_positions_all_backends<T: FilterElement>(
+        input: &[T],
+        range: RangeInclusive<T>,
+        expected: &[u32],
+    ) {
+        let expected_first = expected.first().map(|&id| id as usize);
+        let expected_last = expected.last().map(|&id| id as usize);
+        let expected_any = !expected.is_empty();
+        for_each_backend!(|backend| {
+            assert_eq!(backend::any_in_range(input, range.clone()), expected_any);
+            assert_eq!(backend::position_first_in_range(input, range.clone()), expected_first);
+            assert_eq!(backend::position_last_in_range(input, range.clone()), expected_last);
+        });
+    }
+
+    #[test]
+    fn test_positions_single_match() {
+        for len in [1, 7, 8, 9, 31, 64, 65, 100] {
+            for pos in 0..len {
+                let mut input = vec![0u16; len];
+                input[pos] = 7;
+                check_positions_all_backends(&input, 5..=10, &[pos as u32]);
+                let mut input = vec![0.5f64; len];
+                input[pos] = f64::INFINITY;
+                check_positions_all_backends(&input, 1.0..=f64::INFINITY, &[pos as u32]);
+            }
+        }
+    }
+
+    fn check_all_backends<T: FilterElement + std::fmt::Debug>(input: &[T], range: RangeInclusive<T>) {
+        let mut expected = Vec::new();
+        filter_vec_scalar(input, range.clone(), &mut expected);
+        check_bitset_all_backends(input, range.clone(), &expected);
+        check_values_all_backends(input, range.clone(), &expected);
+        check_count_all_backends(input, range.clone(), expected.len());
+        check_positions_all_backends(input, range.clone(), &expected);
+        check_limit_all_backends(input, range.clone(), &expected);
+        check_slice_all_backends(input, range.clone(), &expected);
+        check_visit_all_backends(input, range.clone(), &expected);
+        let mut output = Vec::new();
+        filter_vec_nobranch(input, range.clone(), &mut output);
+        assert_eq!(output, expected);
+        for_each_backend!(|backend| {
+            backend::filter_vec(input, range.clone(), &mut output);
+            assert_eq!(output, expected);
+        });
+    }
+
+    #[test]
+    fn test_filter_element_types() {
+        let vals: Vec<u64> = (0..75u64).map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15)).collect();
+        for len in [0, 3, 4, 7, 8, 15, 16, 17, 31, 75] {
+            let vals = &vals[..len];
+            check_all_backends(vals, 1 << 62..=u64::MAX - (1 << 60));
+            let signed: Vec<i64> = vals.iter().map(|&v| v as i64).collect();
+            check_all_backends(&signed, -(1 << 62)..=1 << 61);
+            let signed_32: Vec<i32> = vals.iter().map(|&v| (v >> 32) as i32).collect();
+            check_all_backends(&signed_32, i32::MIN / 2..=i32::MAX / 3);
+            let floats: Vec<f64> = signed.iter().map(|&v| v as f64).collect();
+            check_all_backends(&floats, -1e18..=1e18);
+            let mut floats_32: Vec<f32> = signed_32.iter().map(|&v| v as f32).collect();
+            if let Some(first) = floats_32.first_mut() {
+                *first = f32::NAN;
+            }
+            check_all_backends(&floats_32, -1e9..=1e9);
+            check_all_backends(&floats_32, f32::NEG_INFINITY..=f32::INFINITY);
+        }
+    }
+
+    #[test]
+    fn test_filter_narrow_element_types() {
+        let vals: Vec<u16> = (0..200u32).map(|i| (i.wrapping_mul(0x9E37_79B9) >> 16) as u16).collect();
+        for len in [0, 5, 16, 31, 32, 33, 63, 64, 65, 127, 200] {
+            let vals = &vals[..len];
+            check_all_backends(vals, 1000..=50000);
+            check_all_backends(vals, 0x8000..=u16::MAX);
+            let signed: Vec<i16> = vals.iter().map(|&v| v as i16).collect();
+            check_all_backends(&signed, -3000..=20000);
+            let bytes: Vec<u8> = vals.iter().map(|&v| v as u8).collect();
+            check_all_backends(&bytes, 20..=200);
+            check_all_backends(&bytes, 128..=255);
+            let signed_bytes: Vec<i8> = bytes.iter().map(|&v| v as i8).collect();
+            check_all_backends(&signed_bytes, -100..=27);
+        }
+    }
+
+    #[test]
+    fn test_filter_exhaustive_byte_ranges() {
+        // The SIMD kernels test `x - start <= end - start` with wrapping arithmetic:
+        // check every bound against every value, across the sign boundary.
+        let bytes: Vec<u8> = (0..=255).collect();
+        let signed_bytes: Vec<i8> = (-128..=127).collect();
+        for start in (0..=255u8).step_by(3) {
+            for end in (start..=255).step_by(5).chain([255]) {
+                check_all_backends(&bytes, start..=end);
+                check_all_backends(&signed_bytes, start as i8..=end as i8);
+            }
+        }
+    }
+
+    fn check_output_tracks_matches<T: PartialEq + std::fmt::Debug>(output: &[T], capacity: usize, expected: &[T]) {
+        assert_eq!(output, expected);
+        assert!(capacity <= 2 * OUTPUT_BLOCK_LEN, "{capacity} slots reserved");
+    }
+
+    #[test]
+    fn test_output_memory_tracks_matches() {
+        let input: Vec<u32> = (0..1_000_000u32).map(|i| i.wrapping_mul(2_654_435_761)).collect();
+        let range = 1000..=1_000_000;
+        let mut expected = Vec::new();
+        filter_vec_scalar(&input, range.clone(), &mut expected);
+        assert!(expected.len() < 1000);
+        let expected_values: Vec<u32> = expected.iter().map(|&id| input[id as usize]).collect();
+        let mut outside = Vec::new();
+        for_each_backend!(|backend| {
+            let (mut ids, mut values, mut inside) = (Vec::new(), Vec::new(), Vec::new());
+            backend::filter_vec(&input, range.clone(), &mut ids);
+            check_output_tracks_matches(&ids, ids.capacity(), &expected);
+            backend::filter_values(&input, range.clone(), &mut values);
+            check_output_tracks_matches(&values, values.capacity(), &expected_values);
+            backend::partition_by_range(&input, range.clone(), &mut inside, &mut outside);
+            check_output_tracks_matches(&inside, inside.capacity(), &expected);
+            backend::filter_ids_and_values(&input, range.clone(), &mut ids, &mut values);
+            check_output_tracks_matches(&ids, ids.capacity(), &expected);
+            check_output_tracks_matches(&values, values.capacity(), &expected_values);
+        });
+        let mut ids = Vec::new();
+        filter_vec_scalar(&input, range.clone(), &mut ids);
+        check_output_tracks_matches(&ids, ids.capacity(), &expected);
+        let mut ids = Vec::new();
+        filter_vec_nobranch(&input, range.clone(), &mut ids);
+        check_output_tracks_matches(&ids, ids.capacity(), &expected);
+        let mut ids = Vec::new();
+        filter_vec_iter(&input, range, &mut ids);
+        check_output_tracks_matches(&ids, ids.capacity(), &expected);
+    }
+
+    #[test]
+    fn test_filter_across_output_blocks() {
+        let vals: Vec<u16> = (0..3 * OUTPUT_BLOCK_LEN as u32 + 17)
+            .map(|i| (i.wrapping_mul(0x9E37_79B9) >> 16) as u16)
+            .collect();
+        check_all_backends(&vals, 1000..=50000);
+        check_outside_all_backends(&vals, 1000..=50000);
+        let mut ids = Vec::new();
+        filter_vec_with_offset(&vals, 1000..=50000, 100, &mut ids);
+        let expected: Vec<u32> = (0..vals.len() as u32)
+            .filter(|&id| (1000..=50000).contains(&vals[id as usize]))
+            .map(|id| id + 100)
+            .collect();
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn test_range_filter_iter() {
+        for len in [0, 1, 31, 255, 256, 257, 1000] {
+            let vals: Vec<i32> = (0..len as u32)
+                .map(|i| (i.wrapping_mul(0x9E37_79B9) >> 24) as i32 - 128)
+                .collect();
+            let mut expected = Vec::new();
+            filter_vec_scalar(&vals, -50..=70, &mut expected);
+            assert_eq!(RangeFilterIter::new(&vals, -50..=70).collect::<Vec<u32>>(), expected);
+            let mut iter = RangeFilterIter::new(&vals, -50..=70);
+            let mut blocks = Vec::new();
+            while let Some(block) = iter.next_block() {
+                assert!(!block.is_empty());
+                blocks.extend_from_slice(block);
+            }
+            assert_eq!(blocks, expected);
+            assert_eq!(iter.next(), None);
+            assert_eq!(RangeFilterIter::new(&vals, 10..10).next(), None);
+        }
+        let vals: Vec<f32> = vec![1.0, f32::NAN, 3.0, -2.0, 5.0];
+        assert_eq!(RangeFilterIter::new(&vals, ..).collect::<Vec<u32>>(), [0, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_range_filter_iter_stops_early() {
+        let vals: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+        let mut iter = RangeFilterIter::new(&vals, 10..=12);
+        assert_eq!(iter.by_ref().take(4).collect::<Vec<u32>>(), [10, 11, 12, 266]);
+        // Only the second block of 256 elements has been filtered so far.
+        assert_eq!(iter.next_block(), Some(&[267, 268][..]));
+        assert_eq!(iter.next(), Some(522));
+        assert!(iter.size_hint().1.unwrap() < vals.len());
     }
 }
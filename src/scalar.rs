@@ -0,0 +1,326 @@
+//! Portable kernels, used when the CPU supports neither AVX2 nor AVX-512.
+//!
+//! Each function is the portable version of the function of the same name at the crate
+//! root, where its behavior is documented.
+
+use std::mem::MaybeUninit;
+use std::ops::{RangeBounds, RangeInclusive};
+
+use crate::set::U32Set;
+use crate::FilterElement;
+
+/// Portable version of [`crate::filter_vec`].
+pub fn filter_vec<T: FilterElement>(input: &[T], range: impl RangeBounds<T>, output: &mut Vec<u32>) {
+    filter_vec_with_offset(input, range, 0, output);
+}
+
+/// Portable version of [`crate::filter_vec_with_offset`].
+pub fn filter_vec_with_offset<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    first_id: u32,
+    output: &mut Vec<u32>,
+) {
+    output.clear();
+    filter_vec_append(input, range, first_id, output);
+}
+
+/// Portable version of [`crate::filter_vec_append`].
+pub fn filter_vec_append<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    first_id: u32,
+    output: &mut Vec<u32>,
+) {
+    crate::assert_ids_fit(first_id, input.len());
+    let Some(range) = crate::to_inclusive(range) else {
+        return;
+    };
+    append_in_range(input, range, first_id, output);
+}
+
+/// Appends to `output` the ids of the elements of `input` that belong to `range`.
+///
+/// The ids of `input` must fit in a `u32`.
+pub(crate) fn append_in_range<T: FilterElement>(
+    input: &[T],
+    range: RangeInclusive<T>,
+    first_id: u32,
+    output: &mut Vec<u32>,
+) {
+    append_ids(input, |el| range.contains(&el), first_id, output);
+}
+
+/// Appends to `output` the ids of the elements of `input` that pass `predicate`,
+/// without branching on the result of the test.
+pub(crate) fn append_ids<T: Copy>(
+    input: &[T],
+    predicate: impl Fn(T) -> bool,
+    first_id: u32,
+    output: &mut Vec<u32>,
+) {
+    for (block_id, block) in input.chunks(crate::OUTPUT_BLOCK_LEN).enumerate() {
+        let block_first_id = first_id + (block_id * crate::OUTPUT_BLOCK_LEN) as u32;
+        let mut output_len = output.len();
+        output.resize(output_len + block.len(), 0u32);
+        for (id, &el) in block.iter().enumerate() {
+            output[output_len] = block_first_id + id as u32;
+            output_len += if predicate(el) { 1 } else { 0 };
+        }
+        output.truncate(output_len);
+    }
+}
+
+/// Portable version of [`crate::filter_vec_into_slice`].
+pub fn filter_vec_into_slice<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    output: &mut [MaybeUninit<u32>],
+) -> usize {
+    crate::assert_ids_fit(0, input.len());
+    crate::assert_output_fits(input.len(), output.len());
+    let Some(range) = crate::to_inclusive(range) else {
+        return 0;
+    };
+    let mut output_len = 0;
+    for (id, el) in input.iter().enumerate() {
+        output[output_len].write(id as u32);
+        output_len += range.contains(el) as usize;
+    }
+    output_len
+}
+
+/// Portable version of [`crate::filter_vec_visit`].
+pub fn filter_vec_visit<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    mut visit: impl FnMut(&[u32]),
+) {
+    crate::assert_ids_fit(0, input.len());
+    let Some(range) = crate::to_inclusive(range) else {
+        return;
+    };
+    let mut ids = [0u32; crate::VISIT_BLOCK_LEN];
+    for (block_id, block) in input.chunks(crate::VISIT_BLOCK_LEN).enumerate() {
+        let block_first_id = (block_id * crate::VISIT_BLOCK_LEN) as u32;
+        let mut num_ids = 0;
+        for (id, el) in block.iter().enumerate() {
+            ids[num_ids] = block_first_id + id as u32;
+            num_ids += range.contains(el) as usize;
+        }
+        if num_ids > 0 {
+            visit(&ids[..num_ids]);
+        }
+    }
+}
+
+/// Portable version of [`crate::filter_to_bitset`].
+pub fn filter_to_bitset<T: FilterElement>(input: &[T], range: impl RangeBounds<T>, bitset: &mut [u64]) {
+    crate::assert_bitset_fits(input.len(), bitset.len());
+    let Some(range) = crate::to_inclusive(range) else {
+        bitset[..input.len().div_ceil(64)].fill(0);
+        return;
+    };
+    for (chunk, bits) in input.chunks(64).zip(bitset.iter_mut()) {
+        *bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0u64, |bits, (i, el)| bits | ((range.contains(el) as u64) << i));
+    }
+}
+
+/// Portable version of [`crate::count_in_range`].
+pub fn count_in_range<T: FilterElement>(input: &[T], range: impl RangeBounds<T>) -> usize {
+    let Some(range) = crate::to_inclusive(range) else {
+        return 0;
+    };
+    input.iter().filter(|el| range.contains(el)).count()
+}
+
+/// Portable version of [`crate::filter_vec_limit`].
+pub fn filter_vec_limit<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    skip: usize,
+    limit: usize,
+    output: &mut Vec<u32>,
+) {
+    crate::assert_ids_fit(0, input.len());
+    output.clear();
+    let Some(range) = crate::to_inclusive(range) else {
+        return;
+    };
+    output.reserve(limit.min(input.len()));
+    let matching_ids = (0..input.len() as u32).filter(|&id| range.contains(&input[id as usize]));
+    output.extend(matching_ids.skip(skip).take(limit));
+}
+
+/// Portable version of [`crate::any_in_range`].
+pub fn any_in_range<T: FilterElement>(input: &[T], range: impl RangeBounds<T>) -> bool {
+    position_first_in_range(input, range).is_some()
+}
+
+/// Portable version of [`crate::position_first_in_range`].
+pub fn position_first_in_range<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+) -> Option<usize> {
+    let range = crate::to_inclusive(range)?;
+    input.iter().position(|el| range.contains(el))
+}
+
+/// Portable version of [`crate::position_last_in_range`].
+pub fn position_last_in_range<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+) -> Option<usize> {
+    let range = crate::to_inclusive(range)?;
+    input.iter().rposition(|el| range.contains(el))
+}
+
+/// Portable version of [`crate::filter_eq`].
+pub fn filter_eq<T: FilterElement>(input: &[T], value: T, output: &mut Vec<u32>) {
+    crate::assert_ids_fit(0, input.len());
+    output.clear();
+    append_ids(input, |el| el == value, 0, output);
+}
+
+/// Portable version of [`crate::filter_ne`].
+pub fn filter_ne<T: FilterElement>(input: &[T], value: T, output: &mut Vec<u32>) {
+    crate::assert_ids_fit(0, input.len());
+    output.clear();
+    append_ids(input, |el| el != value, 0, output);
+}
+
+/// Portable version of [`crate::filter_vec_multi_range`].
+pub fn filter_vec_multi_range<T: FilterElement>(
+    input: &[T],
+    ranges: &[RangeInclusive<T>],
+    output: &mut Vec<u32>,
+) {
+    crate::assert_ids_fit(0, input.len());
+    output.clear();
+    append_ids(input, |el| ranges.iter().any(|range| range.contains(&el)), 0, output);
+}
+
+/// Portable version of [`crate::filter_vec_outside`].
+pub fn filter_vec_outside<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    output: &mut Vec<u32>,
+) {
+    crate::assert_ids_fit(0, input.len());
+    output.clear();
+    let Some(range) = crate::to_inclusive(range) else {
+        output.extend(0..input.len() as u32);
+        return;
+    };
+    append_ids(input, |el| !range.contains(&el), 0, output);
+}
+
+/// Portable version of [`crate::filter_in_set`].
+pub fn filter_in_set(input: &[u32], values: &[u32], output: &mut Vec<u32>) {
+    crate::assert_ids_fit(0, input.len());
+    output.clear();
+    let set = U32Set::new(values);
+    append_ids(input, |el| set.contains(el), 0, output);
+}
+
+/// Portable version of [`crate::filter_selection`].
+pub fn filter_selection(
+    column: &[u32],
+    candidate_ids: &[u32],
+    range: impl RangeBounds<u32>,
+    output: &mut Vec<u32>,
+) {
+    crate::assert_ids_in_bounds(candidate_ids, column.len());
+    output.clear();
+    let Some(range) = crate::to_inclusive(range) else {
+        return;
+    };
+    output.extend(
+        candidate_ids
+            .iter()
+            .copied()
+            .filter(|&id| range.contains(&column[id as usize])),
+    );
+}
+
+/// Portable version of [`crate::retain_in_range`].
+pub fn retain_in_range<T: FilterElement>(values: &mut Vec<T>, range: impl RangeBounds<T>) {
+    let Some(range) = crate::to_inclusive(range) else {
+        values.clear();
+        return;
+    };
+    values.retain(|el| range.contains(el));
+}
+
+/// Portable version of [`crate::partition_by_range`].
+pub fn partition_by_range<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    inside: &mut Vec<u32>,
+    outside: &mut Vec<u32>,
+) {
+    crate::assert_ids_fit(0, input.len());
+    inside.clear();
+    outside.clear();
+    let Some(range) = crate::to_inclusive(range) else {
+        outside.extend(0..input.len() as u32);
+        return;
+    };
+    for (block_id, block) in input.chunks(crate::OUTPUT_BLOCK_LEN).enumerate() {
+        let block_first_id = (block_id * crate::OUTPUT_BLOCK_LEN) as u32;
+        let mut inside_len = inside.len();
+        let mut outside_len = outside.len();
+        inside.resize(inside_len + block.len(), 0u32);
+        outside.resize(outside_len + block.len(), 0u32);
+        for (id, el) in block.iter().enumerate() {
+            let id = block_first_id + id as u32;
+            let contained = range.contains(el);
+            inside[inside_len] = id;
+            outside[outside_len] = id;
+            inside_len += contained as usize;
+            outside_len += !contained as usize;
+        }
+        inside.truncate(inside_len);
+        outside.truncate(outside_len);
+    }
+}
+
+/// Portable version of [`crate::filter_values`].
+pub fn filter_values<T: FilterElement>(input: &[T], range: impl RangeBounds<T>, output: &mut Vec<T>) {
+    output.clear();
+    let Some(range) = crate::to_inclusive(range) else {
+        return;
+    };
+    output.extend(input.iter().copied().filter(|el| range.contains(el)));
+}
+
+/// Portable version of [`crate::filter_ids_and_values`].
+pub fn filter_ids_and_values<T: FilterElement>(
+    input: &[T],
+    range: impl RangeBounds<T>,
+    ids: &mut Vec<u32>,
+    values: &mut Vec<T>,
+) {
+    crate::assert_ids_fit(0, input.len());
+    ids.clear();
+    values.clear();
+    let Some(range) = crate::to_inclusive(range) else {
+        return;
+    };
+    for (block_id, block) in input.chunks(crate::OUTPUT_BLOCK_LEN).enumerate() {
+        let block_first_id = (block_id * crate::OUTPUT_BLOCK_LEN) as u32;
+        let mut output_len = ids.len();
+        ids.resize(output_len + block.len(), 0u32);
+        values.resize(output_len + block.len(), block[0]);
+        for (id, &el) in block.iter().enumerate() {
+            ids[output_len] = block_first_id + id as u32;
+            values[output_len] = el;
+            output_len += range.contains(&el) as usize;
+        }
+        ids.truncate(output_len);
+        values.truncate(output_len);
+    }
+}
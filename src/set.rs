@@ -0,0 +1,63 @@
+//! Value sets used by `filter_in_set`, and the choice of their lookup strategy.
+
+/// Sets with at most this many values are tested by comparing every element
+/// against each value.
+const BROADCAST_MAX_LEN: usize = 8;
+
+/// Largest span, in bits, of the bitmap built for dense sets (2MB).
+const BITMAP_MAX_SPAN: u64 = 1 << 24;
+
+/// A bitmap is only built if it holds at least one value every
+/// `BITMAP_MAX_BITS_PER_VALUE` bits on average.
+const BITMAP_MAX_BITS_PER_VALUE: u64 = 64;
+
+pub(crate) enum U32Set {
+    Empty,
+    /// Tiny set, every element is compared against each of these sorted values.
+    Broadcast(Vec<u32>),
+    /// Medium set, looked up with a branchless binary search in these sorted values.
+    Sorted(Vec<u32>),
+    /// Dense set over a small domain: bit `v - min` of `words` is set if `v` is in the set.
+    Bitmap { min: u32, span: u32, words: Vec<u32> },
+}
+
+impl U32Set {
+    pub fn new(values: &[u32]) -> U32Set {
+        let mut values = values.to_vec();
+        values.sort_unstable();
+        values.dedup();
+        let (min, max) = match (values.first(), values.last()) {
+            (Some(&min), Some(&max)) => (min, max),
+            _ => return U32Set::Empty,
+        };
+        if values.len() <= BROADCAST_MAX_LEN {
+            return U32Set::Broadcast(values);
+        }
+        let span = (max - min) as u64 + 1;
+        if span <= BITMAP_MAX_SPAN && span <= values.len() as u64 * BITMAP_MAX_BITS_PER_VALUE {
+            let mut words = vec![0u32; span.div_ceil(32) as usize];
+            for &value in &values {
+                let offset = value - min;
+                words[(offset / 32) as usize] |= 1 << (offset % 32);
+            }
+            return U32Set::Bitmap {
+                min,
+                span: span as u32,
+                words,
+            };
+        }
+        U32Set::Sorted(values)
+    }
+
+    pub fn contains(&self, value: u32) -> bool {
+        match self {
+            U32Set::Empty => false,
+            U32Set::Broadcast(values) => values.contains(&value),
+            U32Set::Sorted(values) => values.binary_search(&value).is_ok(),
+            U32Set::Bitmap { min, span, words } => {
+                let offset = value.wrapping_sub(*min);
+                offset < *span && words[(offset / 32) as usize] & (1 << (offset % 32)) != 0
+            }
+        }
+    }
+}